@@ -2,7 +2,9 @@
 //!
 //! Handles audio file upload and analysis via Gemini REST API
 
+use crate::audio::SpeakingSegment;
 use crate::database::AnalysisMode;
+use crate::metrics;
 use reqwest::{multipart, Client};
 use serde::{Deserialize, Serialize};
 use serenity::model::id::UserId;
@@ -133,14 +135,14 @@ struct ContentRequest {
     parts: Vec<PartRequest>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 enum PartRequest {
     Text { text: String },
     FileData { file_data: FileData },
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 struct FileData {
     file_uri: String,
     mime_type: String,
@@ -172,15 +174,21 @@ impl Analyzer {
         Self {
             client,
             api_key,
-            model: "gemini-2.0-flash".to_string(),
+            model: crate::config::models::GEMINI_FLASH.to_string(),
         }
     }
 
+    /// The default model to use when a guild hasn't selected one
+    pub fn default_model(&self) -> &str {
+        &self.model
+    }
+
     /// Upload a file to Gemini File API
     async fn upload_file(&self, path: &PathBuf, mime_type: &str) -> Result<FileInfo, AnalyzerError> {
         let mut file = File::open(path).await?;
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer).await?;
+        let file_size = buffer.len();
 
         let file_name = path.file_name()
             .and_then(|n| n.to_str())
@@ -214,7 +222,11 @@ impl Analyzer {
 
         let upload_response: UploadResponse = response.json().await?;
         info!("Uploaded file: {}", upload_response.file.name);
-        
+
+        metrics::UPLOAD_FILE_SIZE_BYTES
+            .with_label_values(&[mime_type])
+            .observe(file_size as f64);
+
         Ok(upload_response.file)
     }
 
@@ -241,6 +253,7 @@ impl Analyzer {
             tokio::time::sleep(Duration::from_secs(2)).await;
         }
 
+        metrics::FILE_ACTIVE_TIMEOUTS_TOTAL.inc();
         Err(AnalyzerError::Api("File processing timeout".to_string()))
     }
 
@@ -255,13 +268,97 @@ impl Analyzer {
         Ok(())
     }
 
+    /// Send a single `generateContent` request against the given model and
+    /// extract the report text from the response.
+    async fn generate_once(&self, model: &str, content_parts: &[PartRequest]) -> Result<String, AnalyzerError> {
+        let request = GenerateRequest {
+            contents: vec![ContentRequest {
+                role: "user".to_string(),
+                parts: content_parts.to_vec(),
+            }],
+            tools: Some(vec![Tool {
+                google_search: GoogleSearch {},
+            }]),
+        };
+
+        let url = format!(
+            "{}/models/{}:generateContent?key={}",
+            GEMINI_API_BASE, model, self.api_key
+        );
+
+        let timer = metrics::GEMINI_GENERATE_LATENCY_SECONDS
+            .with_label_values(&[model])
+            .start_timer();
+        let response = self.client
+            .post(&url)
+            .json(&request)
+            .send()
+            .await?;
+        timer.observe_duration();
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+
+            if status.as_u16() == 429 || text.contains("Quota exceeded") {
+                metrics::RATE_LIMIT_HITS_TOTAL.inc();
+                return Err(AnalyzerError::RateLimitExceeded);
+            }
+
+            return Err(AnalyzerError::Api(format!("Generation failed: {} - {}", status, text)));
+        }
+
+        let gen_response: GenerateResponse = response.json().await?;
+
+        if let Some(error) = gen_response.error {
+            return Err(AnalyzerError::Api(error.message));
+        }
+
+        let text = gen_response
+            .candidates
+            .and_then(|c| c.into_iter().next())
+            .and_then(|c| c.content.parts.into_iter().next())
+            .and_then(|p| p.text)
+            .unwrap_or_else(|| "分析結果を取得できませんでした。".to_string());
+
+        Ok(text)
+    }
+
+    /// Render a chronologically-merged speaking-segment timeline as plain
+    /// text lines of `[start_ms-end_ms] name`, one per segment, so the model
+    /// can line overlapping segments up visually.
+    fn format_timeline(timeline: &[SpeakingSegment], user_map: &HashMap<UserId, String>) -> String {
+        timeline
+            .iter()
+            .map(|segment| {
+                let name = user_map
+                    .get(&segment.user_id)
+                    .cloned()
+                    .unwrap_or_else(|| format!("User_{}", segment.user_id));
+                format!("[{}ms-{}ms] {}", segment.start_ms, segment.end_ms, name)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     /// Analyze audio files from multiple users
+    ///
+    /// `model` selects the Gemini model to use first (see `config::models`);
+    /// on `RateLimitExceeded` this falls back to the other model (pro→flash
+    /// or flash→pro) once, with a short exponential backoff, before
+    /// surfacing the error to the caller. `timeline` is the chronologically
+    /// merged speaking-segment list produced by `UserRecorder::flush_audio`,
+    /// so the model can reason about turn-taking, overlap and interruptions
+    /// instead of just a bag of independent per-user files.
     pub async fn analyze_discussion(
         &self,
+        guild_id: u64,
         audio_files: HashMap<UserId, PathBuf>,
+        timeline: &[SpeakingSegment],
         context_history: &str,
         user_map: HashMap<UserId, String>,
         mode: AnalysisMode,
+        model: &str,
     ) -> Result<String, AnalyzerError> {
         if audio_files.is_empty() {
             return Err(AnalyzerError::NoAudioFiles);
@@ -283,6 +380,18 @@ impl Analyzer {
             });
         }
 
+        // Add the merged, timestamped turn-taking timeline, so the model can
+        // reason about who interrupted whom rather than guessing from
+        // independent per-user files with no temporal alignment
+        if !timeline.is_empty() {
+            content_parts.push(PartRequest::Text {
+                text: format!(
+                    "発言タイムライン（重複区間は発言がかぶっていたことを示します）:\n{}",
+                    Self::format_timeline(timeline, &user_map)
+                ),
+            });
+        }
+
         // Upload each audio file
         for (user_id, file_path) in &audio_files {
             let user_name = user_map
@@ -322,57 +431,40 @@ impl Analyzer {
             return Err(AnalyzerError::NoAudioFiles);
         }
 
-        // Generate content
-        let request = GenerateRequest {
-            contents: vec![ContentRequest {
-                role: "user".to_string(),
-                parts: content_parts,
-            }],
-            tools: Some(vec![Tool {
-                google_search: GoogleSearch {},
-            }]),
+        // Generate content, falling back to the other model once (with
+        // backoff) if we get rate limited. Reuses the file URIs already
+        // uploaded above rather than re-uploading.
+        let fallback_model = if model == crate::config::models::GEMINI_PRO {
+            crate::config::models::GEMINI_FLASH
+        } else {
+            crate::config::models::GEMINI_PRO
         };
 
-        let url = format!(
-            "{}/models/{}:generateContent?key={}",
-            GEMINI_API_BASE, self.model, self.api_key
-        );
-
-        let response = self.client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await?;
+        let result = match self.generate_once(model, &content_parts).await {
+            Err(AnalyzerError::RateLimitExceeded) => {
+                warn!("Rate limited on {}, retrying on {} after backoff", model, fallback_model);
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                match self.generate_once(fallback_model, &content_parts).await {
+                    Err(AnalyzerError::RateLimitExceeded) => {
+                        tokio::time::sleep(Duration::from_secs(4)).await;
+                        self.generate_once(fallback_model, &content_parts).await
+                    }
+                    other => other,
+                }
+            }
+            other => other,
+        };
 
         // Clean up uploaded files
         for file_name in &uploaded_files {
             let _ = self.delete_file(file_name).await;
         }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            
-            if status.as_u16() == 429 || text.contains("Quota exceeded") {
-                return Err(AnalyzerError::RateLimitExceeded);
-            }
-            
-            return Err(AnalyzerError::Api(format!("Generation failed: {} - {}", status, text)));
-        }
+        let text = result?;
 
-        let gen_response: GenerateResponse = response.json().await?;
-
-        if let Some(error) = gen_response.error {
-            return Err(AnalyzerError::Api(error.message));
-        }
-
-        // Extract text from response
-        let text = gen_response
-            .candidates
-            .and_then(|c| c.into_iter().next())
-            .and_then(|c| c.content.parts.into_iter().next())
-            .and_then(|p| p.text)
-            .unwrap_or_else(|| "分析結果を取得できませんでした。".to_string());
+        metrics::ANALYSES_TOTAL
+            .with_label_values(&[&guild_id.to_string(), mode.as_str()])
+            .inc();
 
         Ok(text)
     }
@@ -2,8 +2,9 @@
 //!
 //! Handles per-user audio recording with Opus encoding
 
+mod ogg_mux;
 pub mod recorder;
 pub mod processor;
 
-pub use recorder::UserRecorder;
+pub use recorder::{SpeakingSegment, UserRecorder};
 pub use processor::AudioProcessor;
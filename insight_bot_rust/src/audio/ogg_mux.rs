@@ -0,0 +1,132 @@
+//! Hand-rolled OGG page muxer for Opus streams
+//!
+//! Used by `AudioProcessor::mux_opus_to_ogg`. Writes the container directly,
+//! without an OGG library, so the exact page layout (capture pattern, lacing
+//! table, CRC) and granule positions are all visible here. Implements just enough of RFC 3533 to produce a valid,
+//! decodable single-stream OGG file: one packet per page.
+
+use once_cell::sync::Lazy;
+use std::io::{self, Write};
+
+const CAPTURE_PATTERN: &[u8; 4] = b"OggS";
+
+/// Header-type flag bits (RFC 3533 section 6)
+const FLAG_BOS: u8 = 0x02;
+const FLAG_EOS: u8 = 0x04;
+
+/// OGG's CRC-32 variant: polynomial 0x04C11DB7, non-reflected, init 0 —
+/// distinct from the common reflected CRC-32 (e.g. zlib's 0xEDB88320).
+static CRC_TABLE: Lazy<[u32; 256]> = Lazy::new(|| {
+    let mut table = [0u32; 256];
+    for (i, slot) in table.iter_mut().enumerate() {
+        let mut r = (i as u32) << 24;
+        for _ in 0..8 {
+            r = if r & 0x8000_0000 != 0 {
+                (r << 1) ^ 0x04c1_1db7
+            } else {
+                r << 1
+            };
+        }
+        *slot = r;
+    }
+    table
+});
+
+fn ogg_crc32(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |crc, &byte| {
+        (crc << 8) ^ CRC_TABLE[(((crc >> 24) ^ byte as u32) & 0xff) as usize]
+    })
+}
+
+/// Lacing (segment) table for a single packet: full 255-byte segments
+/// followed by the terminating segment (always present, even if 0 bytes).
+fn lacing_table(len: usize) -> Vec<u8> {
+    let mut table = vec![255u8; len / 255];
+    table.push((len % 255) as u8);
+    table
+}
+
+/// Write one packet as a single OGG page, computing and patching in its CRC.
+fn write_page<W: Write>(
+    out: &mut W,
+    packet: &[u8],
+    serial: u32,
+    sequence: u32,
+    granule_position: u64,
+    flags: u8,
+) -> io::Result<()> {
+    let segments = lacing_table(packet.len());
+
+    let mut page = Vec::with_capacity(27 + segments.len() + packet.len());
+    page.extend_from_slice(CAPTURE_PATTERN);
+    page.push(0); // stream structure version
+    page.push(flags);
+    page.extend_from_slice(&granule_position.to_le_bytes());
+    page.extend_from_slice(&serial.to_le_bytes());
+    page.extend_from_slice(&sequence.to_le_bytes());
+    page.extend_from_slice(&0u32.to_le_bytes()); // CRC placeholder, patched below
+    page.push(segments.len() as u8);
+    page.extend_from_slice(&segments);
+    page.extend_from_slice(packet);
+
+    let crc = ogg_crc32(&page);
+    page[22..26].copy_from_slice(&crc.to_le_bytes());
+
+    out.write_all(&page)
+}
+
+/// Mux an Opus identification header, comment header, and the recorded
+/// frames into OGG pages: first page carries `OpusHead` (beginning-of-stream),
+/// second carries `OpusTags`, then one page per Opus frame with the granule
+/// position incremented by `samples_per_frame` and the end-of-stream flag set
+/// on the last page.
+pub fn write_opus_stream<W: Write>(
+    out: &mut W,
+    serial: u32,
+    id_header: &[u8],
+    comment_header: &[u8],
+    frames: &[Vec<u8>],
+    samples_per_frame: u64,
+) -> io::Result<()> {
+    let mut sequence = 0u32;
+
+    write_page(out, id_header, serial, sequence, 0, FLAG_BOS)?;
+    sequence += 1;
+    write_page(out, comment_header, serial, sequence, 0, 0)?;
+    sequence += 1;
+
+    let mut granule_position = 0u64;
+    let last_index = frames.len().saturating_sub(1);
+    for (i, frame) in frames.iter().enumerate() {
+        granule_position += samples_per_frame;
+        let flags = if i == last_index { FLAG_EOS } else { 0 };
+        write_page(out, frame, serial, sequence, granule_position, flags)?;
+        sequence += 1;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_written_pages_start_with_capture_pattern() {
+        let mut buf = Vec::new();
+        write_opus_stream(&mut buf, 1, b"OpusHead-fake", b"OpusTags-fake", &[vec![0xAA; 10]], 960).unwrap();
+
+        assert_eq!(&buf[0..4], CAPTURE_PATTERN);
+        // Each page we write here is small enough to fit on one page, so
+        // three "OggS" captures should appear: id header, comment header, frame.
+        let capture_count = buf.windows(4).filter(|w| *w == CAPTURE_PATTERN).count();
+        assert_eq!(capture_count, 3);
+    }
+
+    #[test]
+    fn test_lacing_table_terminates_on_multiple_of_255() {
+        assert_eq!(lacing_table(255), vec![255, 0]);
+        assert_eq!(lacing_table(0), vec![0]);
+        assert_eq!(lacing_table(10), vec![10]);
+    }
+}
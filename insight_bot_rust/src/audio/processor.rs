@@ -2,7 +2,10 @@
 //!
 //! Handles conversion between audio formats if needed for Gemini API
 
-use std::fs;
+use super::ogg_mux;
+use crate::database::RecordingFormat;
+use samplerate::{ConverterType, Samplerate};
+use std::fs::{self, File};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 use tracing::{debug, info, warn};
@@ -11,10 +14,27 @@ use tracing::{debug, info, warn};
 pub enum ProcessorError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
-    #[error("File not found: {0}")]
-    NotFound(PathBuf),
+    #[error("Resample error: {0}")]
+    Resample(String),
+    #[error("Opus decode error: {0}")]
+    Decode(String),
+    #[error("Archival encode error: {0}")]
+    Encode(String),
 }
 
+/// Opus frames captured from Discord are 20ms at 48kHz
+pub const OPUS_FRAME_SAMPLES: u64 = 960;
+/// Single logical Opus stream per saved file; the serial just needs to be
+/// stable within one file, not globally unique
+const OGG_STREAM_SERIAL: u32 = 1;
+
+/// Discord hands us 48 kHz stereo; Gemini only needs speech-quality mono
+pub const SOURCE_SAMPLE_RATE: u32 = 48000;
+pub const SOURCE_CHANNELS: u16 = 2;
+/// Target rate/channels for upload, tunable later per `GuildSettings`
+pub const TARGET_SAMPLE_RATE: u32 = 16000;
+pub const TARGET_CHANNELS: u16 = 1;
+
 /// Audio processor for format operations
 pub struct AudioProcessor;
 
@@ -44,17 +64,170 @@ impl AudioProcessor {
         }
     }
 
-    /// Convert Opus file to OGG container if needed
+    /// Downmix interleaved stereo i16 PCM to mono and resample 48kHz -> 16kHz
     ///
-    /// For now, we keep Opus as-is since Gemini should accept audio/ogg
-    pub fn prepare_for_upload(path: &Path) -> Result<PathBuf, ProcessorError> {
-        if !path.exists() {
-            return Err(ProcessorError::NotFound(path.to_path_buf()));
+    /// Processes the whole buffer in one call rather than per-tick chunks:
+    /// the 48000/16000 ratio is exactly 3:1, but resampling in pieces would
+    /// still introduce boundary artifacts at each chunk edge.
+    pub fn downmix_and_resample(pcm: &[i16]) -> Result<Vec<i16>, ProcessorError> {
+        let mono: Vec<f32> = pcm
+            .chunks_exact(SOURCE_CHANNELS as usize)
+            .map(|frame| {
+                let sum: i32 = frame.iter().map(|&s| s as i32).sum();
+                (sum as f32 / SOURCE_CHANNELS as f32) / i16::MAX as f32
+            })
+            .collect();
+
+        let converter = Samplerate::new(
+            ConverterType::SincMediumQuality,
+            SOURCE_SAMPLE_RATE,
+            TARGET_SAMPLE_RATE,
+            TARGET_CHANNELS as usize,
+        )
+        .map_err(|e| ProcessorError::Resample(e.to_string()))?;
+
+        let resampled = converter
+            .process(&mono)
+            .map_err(|e| ProcessorError::Resample(e.to_string()))?;
+
+        Ok(resampled
+            .into_iter()
+            .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect())
+    }
+
+    /// Build the `OpusHead` identification header (RFC 7845 section 5.1)
+    fn opus_head(channels: u8) -> Vec<u8> {
+        let mut head = Vec::with_capacity(19);
+        head.extend_from_slice(b"OpusHead");
+        head.push(1); // version
+        head.push(channels);
+        head.extend_from_slice(&0u16.to_le_bytes()); // pre-skip
+        head.extend_from_slice(&SOURCE_SAMPLE_RATE.to_le_bytes()); // input sample rate
+        head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+        head.push(0); // channel mapping family
+        head
+    }
+
+    /// Build a minimal `OpusTags` comment header (RFC 7845 section 5.2)
+    fn opus_tags() -> Vec<u8> {
+        let vendor = b"insight_bot_rust";
+        let mut tags = Vec::with_capacity(8 + 4 + vendor.len() + 4);
+        tags.extend_from_slice(b"OpusTags");
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor);
+        tags.extend_from_slice(&0u32.to_le_bytes()); // no user comments
+        tags
+    }
+
+    /// Mux raw Opus frames (as handed to us by Songbird, with no container)
+    /// into a real OGG-Opus file that Gemini and standard decoders can read.
+    pub fn mux_opus_to_ogg(frames: &[Vec<u8>], channels: u8, out_path: &Path) -> Result<(), ProcessorError> {
+        let mut file = File::create(out_path)?;
+
+        ogg_mux::write_opus_stream(
+            &mut file,
+            OGG_STREAM_SERIAL,
+            &Self::opus_head(channels),
+            &Self::opus_tags(),
+            frames,
+            OPUS_FRAME_SAMPLES,
+        )?;
+
+        Ok(())
+    }
+
+    /// Decode raw Opus frames (no OGG container) back to interleaved i16 PCM
+    /// at the original 48kHz capture rate, for archival re-encoding.
+    fn decode_opus_frames(frames: &[Vec<u8>], channels: u8) -> Result<Vec<i16>, ProcessorError> {
+        let opus_channels = if channels == 1 {
+            opus::Channels::Mono
+        } else {
+            opus::Channels::Stereo
+        };
+        let mut decoder = opus::Decoder::new(SOURCE_SAMPLE_RATE, opus_channels)
+            .map_err(|e| ProcessorError::Decode(e.to_string()))?;
+
+        let mut pcm = Vec::with_capacity(frames.len() * OPUS_FRAME_SAMPLES as usize * channels as usize);
+        let mut frame_buf = vec![0i16; OPUS_FRAME_SAMPLES as usize * channels as usize];
+
+        for frame in frames {
+            let decoded_samples = decoder
+                .decode(frame, &mut frame_buf, false)
+                .map_err(|e| ProcessorError::Decode(e.to_string()))?;
+            pcm.extend_from_slice(&frame_buf[..decoded_samples * channels as usize]);
         }
 
-        // For now, just return the path as-is
-        // In a full implementation, we might wrap raw Opus in OGG container
-        Ok(path.to_path_buf())
+        Ok(pcm)
+    }
+
+    /// Write interleaved i16 PCM as a WAV file
+    fn encode_wav(pcm: &[i16], channels: u8, sample_rate: u32, out_path: &Path) -> Result<(), ProcessorError> {
+        let spec = hound::WavSpec {
+            channels: channels as u16,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+
+        let mut writer = hound::WavWriter::create(out_path, spec)
+            .map_err(|e| ProcessorError::Encode(e.to_string()))?;
+        for &sample in pcm {
+            writer
+                .write_sample(sample)
+                .map_err(|e| ProcessorError::Encode(e.to_string()))?;
+        }
+        writer.finalize().map_err(|e| ProcessorError::Encode(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Re-encode a user's raw Opus frames into the guild's chosen archival
+    /// format, writing the result alongside the muxed OGG file. Returns
+    /// `None` for `RecordingFormat::Opus`, since `save_opus_frames`'s OGG
+    /// output already *is* the archival copy in that case.
+    pub fn archive_opus_frames(
+        frames: &[Vec<u8>],
+        channels: u8,
+        format: RecordingFormat,
+        ogg_path: &Path,
+    ) -> Result<Option<PathBuf>, ProcessorError> {
+        if format == RecordingFormat::Opus {
+            return Ok(None);
+        }
+
+        let out_path = ogg_path.with_extension(format.as_str());
+
+        match format {
+            RecordingFormat::Opus => unreachable!(),
+            RecordingFormat::Wav => {
+                let pcm = Self::decode_opus_frames(frames, channels)?;
+                Self::encode_wav(&pcm, channels, SOURCE_SAMPLE_RATE, &out_path)?;
+            }
+            RecordingFormat::Flac | RecordingFormat::Mp3 => {
+                return Err(ProcessorError::Encode(format!(
+                    "{} archival encoding is not yet implemented",
+                    format.as_str()
+                )));
+            }
+        }
+
+        Ok(Some(out_path))
+    }
+
+    /// Downsize a user's raw Opus frames to 16kHz mono WAV before upload, to
+    /// cut bandwidth and Gemini token usage versus the full 48kHz-stereo
+    /// OGG. Takes `frames` directly rather than reading
+    /// back `ogg_path`, since decoding an already-muxed OGG file would need
+    /// a demuxer we don't have; `ogg_path` is only used to name the output.
+    pub fn prepare_for_upload(frames: &[Vec<u8>], channels: u8, ogg_path: &Path) -> Result<PathBuf, ProcessorError> {
+        let pcm = Self::decode_opus_frames(frames, channels)?;
+        let downsampled = Self::downmix_and_resample(&pcm)?;
+
+        let upload_path = ogg_path.with_extension("upload.wav");
+        Self::encode_wav(&downsampled, TARGET_CHANNELS as u8, TARGET_SAMPLE_RATE, &upload_path)?;
+
+        Ok(upload_path)
     }
 
     /// Clean up temporary audio files
@@ -94,4 +267,15 @@ mod tests {
         assert_eq!(AudioProcessor::get_mime_type(Path::new("test.opus")), "audio/ogg");
         assert_eq!(AudioProcessor::get_mime_type(Path::new("test.mp3")), "audio/mp3");
     }
+
+    #[test]
+    fn test_downmix_and_resample_silence() {
+        // 1 second of silent 48kHz stereo should downmix/resample to ~1 second of silence at 16kHz
+        let pcm = vec![0i16; SOURCE_SAMPLE_RATE as usize * SOURCE_CHANNELS as usize];
+        let resampled = AudioProcessor::downmix_and_resample(&pcm).unwrap();
+
+        assert!(resampled.iter().all(|&s| s == 0));
+        let expected_len = SOURCE_SAMPLE_RATE as usize / (SOURCE_SAMPLE_RATE / TARGET_SAMPLE_RATE) as usize;
+        assert!((resampled.len() as i64 - expected_len as i64).abs() < 100);
+    }
 }
@@ -2,12 +2,14 @@
 //!
 //! Records Discord voice audio and saves directly as Opus/OGG files
 
+use crate::audio::processor::AudioProcessor;
+use crate::database::RecordingFormat;
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use serenity::model::id::UserId;
 use songbird::events::context_data::VoiceTick;
 use std::collections::HashMap;
-use std::fs::{self, File};
+use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -21,46 +23,138 @@ pub enum RecorderError {
     Io(#[from] std::io::Error),
     #[error("No audio data available")]
     NoData,
+    #[error("Audio processing failed: {0}")]
+    Processing(#[from] crate::audio::processor::ProcessorError),
+}
+
+/// A gap between two frames' capture times longer than this is treated as a
+/// turn boundary rather than normal jitter between consecutive Opus frames.
+const TURN_BOUNDARY_GAP_MS: u64 = 400;
+
+/// A contiguous span during which a user was speaking, derived from gaps
+/// between their frames' capture times. Segments from different users are
+/// merged and sorted by `start_ms` so the analyzer can reason about
+/// turn-taking, overlap and interruptions across the whole session.
+#[derive(Debug, Clone)]
+pub struct SpeakingSegment {
+    pub user_id: UserId,
+    pub start_ms: u64,
+    pub end_ms: u64,
 }
 
 /// Per-user audio buffer
 struct UserAudioBuffer {
-    /// Raw Opus frames received from Discord
-    opus_frames: Vec<Vec<u8>>,
-    /// Timestamp when recording started for this user
-    start_time: u64,
+    /// Raw Opus frames received from Discord, each alongside the elapsed ms
+    /// (since `start_time_ms`) at which it was captured
+    frames: Vec<(u64, Vec<u8>)>,
+    /// Wall-clock time (ms since UNIX epoch) this buffer started recording,
+    /// used as the zero point for each frame's capture offset
+    start_time_ms: u64,
+    /// How many leading frames of `frames` are already checkpointed to
+    /// disk, so `checkpoint_to_disk` only appends what's new
+    checkpointed: usize,
 }
 
 impl UserAudioBuffer {
     fn new() -> Self {
         Self {
-            opus_frames: Vec::new(),
-            start_time: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            frames: Vec::new(),
+            start_time_ms: now_ms(),
+            checkpointed: 0,
+        }
+    }
+
+    /// Rebuild a buffer from a checkpoint log, keeping the *original*
+    /// pre-crash `start_time_ms` rather than stamping a fresh one. Frames
+    /// added after this (via `add_frame`) stay offset from that same
+    /// zero-point, so the downtime between crash and restart shows up as one
+    /// large, correctly-detected gap in `segments_from_frames` instead of
+    /// silently stitching pre-crash and post-crash speech into one segment.
+    fn restored(start_time_ms: u64) -> Self {
+        Self {
+            frames: Vec::new(),
+            start_time_ms,
+            checkpointed: 0,
         }
     }
 
     fn add_frame(&mut self, data: Vec<u8>) {
-        self.opus_frames.push(data);
+        let offset_ms = now_ms().saturating_sub(self.start_time_ms);
+        self.frames.push((offset_ms, data));
     }
 
     fn is_empty(&self) -> bool {
-        self.opus_frames.is_empty()
+        self.frames.is_empty()
     }
 
-    fn take_frames(&mut self) -> Vec<Vec<u8>> {
-        std::mem::take(&mut self.opus_frames)
+    fn take_frames(&mut self) -> Vec<(u64, Vec<u8>)> {
+        std::mem::take(&mut self.frames)
     }
 }
 
+/// Current wall-clock time in milliseconds since the UNIX epoch
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// Derive speaking segments for one user from their frames' capture offsets:
+/// consecutive frames stay in the same segment unless the gap between them
+/// exceeds `TURN_BOUNDARY_GAP_MS`, in which case a new segment starts.
+/// `frame_duration_ms` extends each segment's end past its last frame so a
+/// single frame still yields a non-zero-length segment.
+fn segments_from_frames(
+    user_id: UserId,
+    frame_times: &[u64],
+    frame_duration_ms: u64,
+) -> Vec<SpeakingSegment> {
+    let mut segments = Vec::new();
+    let mut segment_start = None;
+    let mut last_time = None;
+
+    for &t in frame_times {
+        match (segment_start, last_time) {
+            (None, _) => {
+                segment_start = Some(t);
+            }
+            (Some(start), Some(prev)) if t.saturating_sub(prev) > TURN_BOUNDARY_GAP_MS => {
+                segments.push(SpeakingSegment {
+                    user_id,
+                    start_ms: start,
+                    end_ms: prev + frame_duration_ms,
+                });
+                segment_start = Some(t);
+            }
+            _ => {}
+        }
+        last_time = Some(t);
+    }
+
+    if let (Some(start), Some(last)) = (segment_start, last_time) {
+        segments.push(SpeakingSegment {
+            user_id,
+            start_ms: start,
+            end_ms: last + frame_duration_ms,
+        });
+    }
+
+    segments
+}
+
 /// User-specific audio recorder
 ///
 /// Collects Opus audio frames from Discord and saves them as OGG files
 pub struct UserRecorder {
-    /// Per-user audio buffers
+    /// Per-user audio buffers, keyed by the resolved `UserId` once known, or
+    /// by a pending placeholder (`UserId::new(ssrc as u64)`) until then
     user_buffers: DashMap<UserId, UserAudioBuffer>,
+    /// SSRC -> resolved UserId, populated from `SpeakingStateUpdate`
+    ssrc_map: DashMap<u32, UserId>,
+    /// Guild this recorder belongs to, used to namespace checkpoint files
+    /// within the shared `temp_dir`
+    guild_id: u64,
     /// Temporary audio directory
     temp_dir: PathBuf,
     /// Session timestamp for unique filenames
@@ -68,23 +162,61 @@ pub struct UserRecorder {
 }
 
 impl UserRecorder {
-    /// Create a new recorder
-    pub fn new<P: AsRef<Path>>(temp_dir: P) -> Result<Self, RecorderError> {
+    /// Create a new recorder, restoring any checkpointed audio left behind
+    /// by a crash or restart during a previous recording for this guild
+    pub fn new<P: AsRef<Path>>(guild_id: u64, temp_dir: P) -> Result<Self, RecorderError> {
         let temp_dir = temp_dir.as_ref().to_path_buf();
-        
+
         // Ensure temp directory exists
         fs::create_dir_all(&temp_dir)?;
-        
+
         let session_timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
-        Ok(Self {
+        let recorder = Self {
             user_buffers: DashMap::new(),
+            ssrc_map: DashMap::new(),
+            guild_id,
             temp_dir,
             session_timestamp,
-        })
+        };
+        recorder.restore_checkpoints()?;
+
+        Ok(recorder)
+    }
+
+    /// Resolve an SSRC's placeholder key until a real mapping is known
+    fn pending_key(ssrc: u32) -> UserId {
+        UserId::new(ssrc as u64)
+    }
+
+    /// Record the SSRC -> UserId association delivered by Songbird's
+    /// `SpeakingStateUpdate` event, re-keying any audio already buffered
+    /// under the SSRC's pending placeholder so it isn't lost or misattributed.
+    pub fn register_speaker(&self, ssrc: u32, user_id: UserId) {
+        self.ssrc_map.insert(ssrc, user_id);
+
+        let pending = Self::pending_key(ssrc);
+        if pending == user_id {
+            return;
+        }
+
+        if let Some((_, mut buffer)) = self.user_buffers.remove(&pending) {
+            // Rebase the moved frames' offsets onto the target buffer's zero-point
+            let start = buffer.start_time_ms;
+            let mut entry = self.user_buffers.entry(user_id).or_insert_with(|| UserAudioBuffer::restored(start));
+            let base = entry.start_time_ms;
+            entry.frames.extend(buffer.frames.drain(..).map(|(t, f)| ((start + t).saturating_sub(base), f)));
+            debug!("Re-keyed pending SSRC {} buffer to user {}", ssrc, user_id);
+        }
+    }
+
+    /// Drop the SSRC mapping(s) for a user who left the call, per
+    /// `CoreEvent::ClientDisconnect`. Already-buffered audio is left in place.
+    pub fn remove_speaker(&self, user_id: UserId) {
+        self.ssrc_map.retain(|_, mapped| *mapped != user_id);
     }
 
     /// Process incoming voice tick from Songbird
@@ -92,8 +224,13 @@ impl UserRecorder {
     /// This is called for each voice packet received
     pub fn process_voice_tick(&self, tick: &VoiceTick) {
         for (ssrc, data) in &tick.speaking {
-            // Use SSRC as temporary User ID (u32 -> u64)
-            let user_id = UserId::new(*ssrc as u64);
+            // Resolve via the SSRC map if `SpeakingStateUpdate` has already
+            // told us who this is; otherwise buffer under a pending placeholder
+            let user_id = self
+                .ssrc_map
+                .get(ssrc)
+                .map(|r| *r.value())
+                .unwrap_or_else(|| Self::pending_key(*ssrc));
 
             if let Some(packet) = &data.packet {
                 let payload = &packet.payload;
@@ -125,11 +262,151 @@ impl UserRecorder {
         std::io::stdout().flush().ok();
     }
 
+    /// Merge any buffers still sitting under a pending SSRC placeholder into
+    /// their now-known `UserId`, in case a `register_speaker` call and a
+    /// concurrent `process_voice_tick` raced and left a buffer un-rekeyed.
+    /// SSRCs with no mapping yet are left under their raw-SSRC fallback key.
+    fn remap_pending_buffers(&self) {
+        for entry in self.ssrc_map.iter() {
+            let ssrc = *entry.key();
+            let user_id = *entry.value();
+            let pending = Self::pending_key(ssrc);
+
+            if pending == user_id {
+                continue;
+            }
+
+            if let Some((_, mut buffer)) = self.user_buffers.remove(&pending) {
+                // Rebase the moved frames' offsets onto the target buffer's zero-point
+                let start = buffer.start_time_ms;
+                let mut target = self.user_buffers.entry(user_id).or_insert_with(|| UserAudioBuffer::restored(start));
+                let base = target.start_time_ms;
+                target.frames.extend(buffer.frames.drain(..).map(|(t, f)| ((start + t).saturating_sub(base), f)));
+                debug!("Flush-time remap: merged pending SSRC {} buffer into user {}", ssrc, user_id);
+            }
+        }
+    }
+
+    /// Path of a user's durable checkpoint log, namespaced by guild so the
+    /// shared `temp_dir` doesn't collide across concurrently recording guilds
+    fn checkpoint_path(&self, user_id: UserId) -> PathBuf {
+        self.temp_dir.join(format!("{}_{}.checkpoint", self.guild_id, user_id.get()))
+    }
+
+    /// Append any Opus frames received since the last checkpoint to each
+    /// user's durable checkpoint log, so a crash mid-session loses at most
+    /// the interval between checkpoints instead of the whole buffer. Meant
+    /// to be called periodically, independent of `flush_audio`. The log's
+    /// first 8 bytes are the buffer's `start_time_ms`, written once when the
+    /// file is created, so `restore_checkpoints` can rebase restored frames
+    /// onto the same zero-point they were originally captured against.
+    pub fn checkpoint_to_disk(&self) -> Result<(), RecorderError> {
+        for mut entry in self.user_buffers.iter_mut() {
+            let user_id = *entry.key();
+            let buffer = entry.value_mut();
+            if buffer.frames.len() <= buffer.checkpointed {
+                continue;
+            }
+
+            let path = self.checkpoint_path(user_id);
+            let is_new_file = !path.exists();
+
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?;
+            if is_new_file {
+                file.write_all(&buffer.start_time_ms.to_le_bytes())?;
+            }
+            for (offset_ms, frame) in &buffer.frames[buffer.checkpointed..] {
+                file.write_all(&offset_ms.to_le_bytes())?;
+                file.write_all(&(frame.len() as u32).to_le_bytes())?;
+                file.write_all(frame)?;
+            }
+            buffer.checkpointed = buffer.frames.len();
+        }
+
+        Ok(())
+    }
+
+    /// Reload any length-prefixed Opus frames left in this guild's
+    /// checkpoint logs (from a crash before they were ever flushed) back
+    /// into the in-memory buffers, restoring each buffer's original
+    /// `start_time_ms` from the log's header so restored frames and any
+    /// newly-captured ones share one consistent zero-point, then remove the
+    /// logs.
+    fn restore_checkpoints(&self) -> Result<(), RecorderError> {
+        for entry in fs::read_dir(&self.temp_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("checkpoint") {
+                continue;
+            }
+
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let Some((guild_str, user_str)) = stem.split_once('_') else {
+                continue;
+            };
+            if guild_str.parse::<u64>().ok() != Some(self.guild_id) {
+                continue;
+            }
+            let Ok(user_id) = user_str.parse::<u64>() else {
+                continue;
+            };
+            let user_id = UserId::new(user_id);
+
+            let data = fs::read(&path)?;
+            if data.len() < 8 {
+                fs::remove_file(&path)?;
+                continue;
+            }
+            let start_time_ms = u64::from_le_bytes(data[0..8].try_into().unwrap());
+
+            let mut frames = Vec::new();
+            let mut offset = 8;
+            while offset + 12 <= data.len() {
+                let offset_ms = u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+                offset += 8;
+                let len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+                offset += 4;
+                if offset + len > data.len() {
+                    break;
+                }
+                frames.push((offset_ms, data[offset..offset + len].to_vec()));
+                offset += len;
+            }
+
+            if !frames.is_empty() {
+                let mut buffer = self
+                    .user_buffers
+                    .entry(user_id)
+                    .or_insert_with(|| UserAudioBuffer::restored(start_time_ms));
+                buffer.frames.extend(frames);
+                buffer.checkpointed = buffer.frames.len();
+                info!("Restored checkpointed audio for user {} in guild {}", user_id, self.guild_id);
+            }
+
+            fs::remove_file(&path)?;
+        }
+
+        Ok(())
+    }
+
     /// Flush all user audio to files
     ///
-    /// Returns a map of user_id -> file_path for saved audio
-    pub async fn flush_audio(&self) -> Result<HashMap<UserId, PathBuf>, RecorderError> {
+    /// Returns a map of user_id -> file_path for saved audio (always the
+    /// downsized 16kHz mono WAV used for analysis upload, regardless of
+    /// `archive_format`), alongside the chronologically-merged
+    /// speaking-segment timeline derived from each user's frame capture times.
+    pub async fn flush_audio(
+        &self,
+        archive_format: RecordingFormat,
+    ) -> Result<(HashMap<UserId, PathBuf>, Vec<SpeakingSegment>), RecorderError> {
+        self.remap_pending_buffers();
+
         let mut saved_files = HashMap::new();
+        let mut timeline = Vec::new();
         let current_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
@@ -137,6 +414,9 @@ impl UserRecorder {
 
         // Collect user IDs to process
         let user_ids: Vec<UserId> = self.user_buffers.iter().map(|r| *r.key()).collect();
+        // Shared zero-point for every user's segments, so the merged timeline
+        // lines different users' speech up against each other
+        let timeline_base_ms = self.user_buffers.iter().map(|r| r.start_time_ms).min().unwrap_or(0);
 
         for user_id in user_ids {
             if let Some((_, mut buffer)) = self.user_buffers.remove(&user_id) {
@@ -144,24 +424,39 @@ impl UserRecorder {
                     continue;
                 }
 
-                let frames = buffer.take_frames();
+                let frame_times: Vec<u64> = buffer
+                    .frames
+                    .iter()
+                    .map(|(t, _)| (buffer.start_time_ms + *t).saturating_sub(timeline_base_ms))
+                    .collect();
+                let frames: Vec<Vec<u8>> = buffer.take_frames().into_iter().map(|(_, f)| f).collect();
                 let filename = format!(
                     "{}_{}_{}",
                     self.session_timestamp,
                     user_id.get(),
                     current_time
                 );
-                
+
                 // Save as raw Opus data (we'll wrap in OGG container)
-                match self.save_opus_frames(&filename, &frames) {
+                match self.save_opus_frames(&filename, &frames, archive_format) {
                     Ok(path) => {
                         info!("Saved audio for user {} to {:?}", user_id, path);
                         saved_files.insert(user_id, path);
+                        timeline.extend(segments_from_frames(
+                            user_id,
+                            &frame_times,
+                            crate::audio::processor::OPUS_FRAME_SAMPLES * 1000
+                                / crate::audio::processor::SOURCE_SAMPLE_RATE as u64,
+                        ));
                     }
                     Err(e) => {
                         error!("Failed to save audio for user {}: {}", user_id, e);
                     }
                 }
+
+                // These frames are now in a saved file either way; drop the
+                // now-redundant checkpoint log so it isn't restored again.
+                let _ = fs::remove_file(self.checkpoint_path(user_id));
             }
         }
 
@@ -169,27 +464,37 @@ impl UserRecorder {
             return Err(RecorderError::NoData);
         }
 
-        Ok(saved_files)
+        timeline.sort_by_key(|s| s.start_ms);
+
+        Ok((saved_files, timeline))
     }
 
-    /// Save Opus frames to an OGG file
-    fn save_opus_frames(&self, filename: &str, frames: &[Vec<u8>]) -> Result<PathBuf, RecorderError> {
+    /// Mux buffered Opus frames into a real OGG-Opus file, re-encode a
+    /// long-term archival copy if the guild selected a format other than
+    /// Opus, then downsize to a 16kHz mono WAV for analysis upload. The full
+    /// OGG is dropped once the downsized copy exists unless it's doubling as
+    /// the archival file (`archive_format == Opus`).
+    fn save_opus_frames(&self, filename: &str, frames: &[Vec<u8>], archive_format: RecordingFormat) -> Result<PathBuf, RecorderError> {
         let ogg_path = self.temp_dir.join(format!("{}.ogg", filename));
-        
-        // For now, save raw Opus frames concatenated
-        // In a full implementation, we'd properly wrap in OGG container
-        let opus_path = self.temp_dir.join(format!("{}.opus", filename));
-        
-        let mut file = File::create(&opus_path)?;
-        for frame in frames {
-            // Write frame length as u16 little-endian, then frame data
-            let len = frame.len() as u16;
-            file.write_all(&len.to_le_bytes())?;
-            file.write_all(frame)?;
+        let channels = crate::audio::processor::SOURCE_CHANNELS as u8;
+
+        AudioProcessor::mux_opus_to_ogg(frames, channels, &ogg_path)?;
+        info!("Muxed {} Opus frames into {:?}", frames.len(), ogg_path);
+
+        match AudioProcessor::archive_opus_frames(frames, channels, archive_format, &ogg_path) {
+            Ok(Some(archive_path)) => info!("Archived recording as {:?}", archive_path),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to archive recording in {:?} format: {}", archive_format, e),
         }
-        
-        info!("Saved {} Opus frames to {:?}", frames.len(), opus_path);
-        Ok(opus_path)
+
+        let upload_path = AudioProcessor::prepare_for_upload(frames, channels, &ogg_path)?;
+        info!("Prepared downsized upload copy {:?}", upload_path);
+
+        if archive_format != RecordingFormat::Opus {
+            let _ = fs::remove_file(&ogg_path);
+        }
+
+        Ok(upload_path)
     }
 
     /// Clear all buffers without saving
@@ -216,7 +521,7 @@ mod tests {
     #[tokio::test]
     async fn test_recorder_basic() {
         let temp = tempdir().unwrap();
-        let recorder = UserRecorder::new(temp.path()).unwrap();
+        let recorder = UserRecorder::new(1, temp.path()).unwrap();
         
         let user_id = UserId::new(12345);
         recorder.add_opus_packet(user_id, &[0x00, 0x01, 0x02, 0x03]);
@@ -225,8 +530,23 @@ mod tests {
         assert!(recorder.has_data());
         assert_eq!(recorder.user_count(), 1);
         
-        let files = recorder.flush_audio().await.unwrap();
+        let (files, timeline) = recorder.flush_audio(RecordingFormat::Opus).await.unwrap();
         assert_eq!(files.len(), 1);
         assert!(files.contains_key(&user_id));
+        assert_eq!(timeline.len(), 1);
+        assert_eq!(timeline[0].user_id, user_id);
+    }
+
+    #[test]
+    fn test_segments_from_frames_splits_on_gap() {
+        let user_id = UserId::new(1);
+        let frame_times = vec![0, 20, 40, 1000, 1020];
+        let segments = segments_from_frames(user_id, &frame_times, 20);
+
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].start_ms, 0);
+        assert_eq!(segments[0].end_ms, 60);
+        assert_eq!(segments[1].start_ms, 1000);
+        assert_eq!(segments[1].end_ms, 1040);
     }
 }
@@ -1,100 +1,29 @@
-//! Discord Bot event handler and voice receive handler
+//! Discord bot wiring: poise framework setup and the songbird voice receive handler
 
 use crate::audio::UserRecorder;
 use crate::commands;
 use crate::config::Config;
-use crate::database::Database;
+use crate::database::{self, Database};
 use crate::session::SessionManager;
-use serenity::all::{
-    Client, Context, EventHandler, GatewayIntents, GuildId, Interaction, Ready,
-};
+use serenity::all::{Client, GatewayIntents, GuildId, UserId};
 use serenity::async_trait;
-use songbird::events::{Event, EventContext, EventHandler as VoiceEventHandler, TrackEvent};
-use songbird::{CoreEvent, SerenityInit};
+use songbird::events::{Event, EventContext, EventHandler as VoiceEventHandler};
+use songbird::SerenityInit;
 use std::sync::Arc;
-use tracing::{error, info};
+use tracing::info;
 
-/// Bot state shared across handlers
-pub struct BotState {
+/// Shared state threaded through every poise command via `Context::data()`
+pub struct Data {
     pub config: Arc<Config>,
     pub db: Arc<Database>,
     pub session_manager: Arc<SessionManager>,
 }
 
-/// Main event handler for the bot
-pub struct Handler {
-    pub state: Arc<BotState>,
-}
-
-#[async_trait]
-impl EventHandler for Handler {
-    async fn ready(&self, ctx: Context, ready: Ready) {
-        info!("Logged in as {}", ready.user.name);
-
-        // Register commands
-        let commands = vec![
-            commands::analyze::register(),
-            commands::settings::register(),
-        ]
-        .into_iter()
-        .flatten()
-        .collect::<Vec<_>>();
-
-        // If guild ID is set, register to specific guild (faster for dev)
-        if let Some(guild_id) = self.state.config.guild_id {
-            let guild = GuildId::new(guild_id);
-            match guild.set_commands(&ctx.http, commands).await {
-                Ok(cmds) => info!("Registered {} guild commands", cmds.len()),
-                Err(e) => error!("Failed to register guild commands: {}", e),
-            }
-        } else {
-            // Register globally
-            match serenity::all::Command::set_global_commands(&ctx.http, commands).await {
-                Ok(cmds) => info!("Registered {} global commands", cmds.len()),
-                Err(e) => error!("Failed to register global commands: {}", e),
-            }
-        }
-    }
+/// Error type used by all poise commands
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
 
-    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        if let Interaction::Command(command) = interaction {
-            let result = match command.data.name.as_str() {
-                "analyze_start" => {
-                    commands::analyze::handle_start(
-                        &ctx,
-                        &command,
-                        self.state.session_manager.clone(),
-                    )
-                    .await
-                }
-                "analyze_stop" => {
-                    commands::analyze::handle_stop(
-                        &ctx,
-                        &command,
-                        self.state.session_manager.clone(),
-                    )
-                    .await
-                }
-                "analyze_now" => {
-                    commands::analyze::handle_now(
-                        &ctx,
-                        &command,
-                        self.state.session_manager.clone(),
-                    )
-                    .await
-                }
-                "settings" => {
-                    commands::settings::handle(&ctx, &command, self.state.db.clone()).await
-                }
-                _ => Ok(()),
-            };
-
-            if let Err(e) = result {
-                error!("Command error: {}", e);
-            }
-        }
-    }
-}
+/// Poise command context, aliased for brevity in `commands/*`
+pub type Context<'a> = poise::Context<'a, Data, Error>;
 
 /// Voice receive event handler
 pub struct VoiceReceiver {
@@ -104,77 +33,98 @@ pub struct VoiceReceiver {
 #[async_trait]
 impl VoiceEventHandler for VoiceReceiver {
     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
-        if let EventContext::VoiceTick(tick) = ctx {
-            // Process each speaking user's audio
-            for (ssrc, data) in &tick.speaking {
-                // Get the decoded voice data if available
-                if let Some(decoded) = &data.decoded_voice {
-                    // We have PCM data, but we want to save Opus directly
-                    // For raw Opus, we'd need to access the packet before decoding
-                    // For now, we'll note that Songbird provides decoded PCM by default
-                    
-                    // In a full implementation, we'd configure Songbird to give us raw Opus
-                    // For now, we'll re-encode PCM to Opus (less efficient but works)
-                }
-                
-                // If we have the original Opus packet (requires special Songbird config)
-                if let Some(packet) = &data.packet {
-                    // This contains the raw Opus data
-                    // We can save this directly for maximum efficiency
+        match ctx {
+            EventContext::VoiceTick(tick) => {
+                self.recorder.process_voice_tick(tick);
+            }
+            EventContext::SpeakingStateUpdate(update) => {
+                if let Some(user_id) = update.user_id {
+                    self.recorder
+                        .register_speaker(update.ssrc, UserId::new(u64::from(user_id)));
                 }
             }
+            EventContext::ClientDisconnect(disconnect) => {
+                self.recorder
+                    .remove_speaker(UserId::new(u64::from(disconnect.user_id)));
+            }
+            _ => {}
         }
-        
+
         None
     }
 }
 
 /// Create and run the Discord bot
-pub async fn run(config: Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub async fn run(config: Config) -> Result<(), Error> {
     let config = Arc::new(config);
-    
-    // Initialize database
-    let db = Arc::new(Database::open("bot_settings.db")?);
-    
+
+    // Initialize database (backend selected by Config::db_type)
+    let db = database::open(&config).await?;
+
     // Create session manager
     let session_manager = Arc::new(SessionManager::new(config.clone(), db.clone()));
-    
-    // Create bot state
-    let state = Arc::new(BotState {
-        config: config.clone(),
-        db,
-        session_manager,
-    });
-
-    // Create handler
-    let handler = Handler {
-        state: state.clone(),
-    };
+
+    let setup_config = config.clone();
+    let setup_db = db.clone();
+    let setup_session_manager = session_manager.clone();
+
+    let framework = poise::Framework::builder()
+        .options(poise::FrameworkOptions {
+            commands: vec![
+                commands::analyze::analyze_start(),
+                commands::analyze::analyze_stop(),
+                commands::analyze::analyze_now(),
+                commands::settings::settings(),
+            ],
+            ..Default::default()
+        })
+        .setup(move |ctx, ready, framework| {
+            let config = setup_config.clone();
+            let db = setup_db.clone();
+            let session_manager = setup_session_manager.clone();
+            Box::pin(async move {
+                info!("Logged in as {}", ready.user.name);
+
+                // If guild ID is set, register to specific guild (faster for dev)
+                if let Some(guild_id) = config.guild_id {
+                    poise::builtins::register_in_guild(
+                        ctx,
+                        &framework.options().commands,
+                        GuildId::new(guild_id),
+                    )
+                    .await?;
+                    info!("Registered commands to guild {}", guild_id);
+                } else {
+                    poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+                    info!("Registered commands globally");
+                }
+
+                // Rejoin and resume any sessions still active from before a
+                // restart, so a crash or redeploy doesn't orphan a recording
+                if let Err(e) = session_manager.resume_sessions(ctx).await {
+                    tracing::warn!("Failed to resume sessions: {}", e);
+                }
+
+                Ok(Data {
+                    config,
+                    db,
+                    session_manager,
+                })
+            })
+        })
+        .build();
 
     // Create client with voice support
     let intents = GatewayIntents::non_privileged() | GatewayIntents::GUILD_VOICE_STATES;
-    
+
     let mut client = Client::builder(&config.discord_token, intents)
-        .event_handler(handler)
+        .framework(framework)
         .register_songbird()
         .await?;
 
-    // Store state in client data
-    {
-        let mut data = client.data.write().await;
-        data.insert::<BotStateKey>(state);
-    }
-
     // Start the client
     info!("Starting bot...");
     client.start().await?;
 
     Ok(())
 }
-
-/// Type key for storing BotState in client data
-pub struct BotStateKey;
-
-impl serenity::prelude::TypeMapKey for BotStateKey {
-    type Value = Arc<BotState>;
-}
@@ -1,143 +1,172 @@
 //! Analyze commands: /analyze_start, /analyze_stop, /analyze_now
 
-use serenity::all::{
-    CommandInteraction, Context, CreateCommand, CreateInteractionResponse,
-    CreateInteractionResponseMessage, EditInteractionResponse, CreateMessage,
-};
-use std::sync::Arc;
+use crate::bot::{Context, Error};
+use serenity::all::Permissions;
 use tracing::info;
 
-use crate::session::SessionManager;
-
-/// Register analyze commands
-pub fn register() -> Vec<CreateCommand> {
-    vec![
-        CreateCommand::new("analyze_start")
-            .description("ボイスチャットの分析を開始します"),
-        CreateCommand::new("analyze_stop")
-            .description("分析を終了し、ボイスチャットから退出します"),
-        CreateCommand::new("analyze_now")
-            .description("すぐにレポートを作成します（分析間隔を待たずに実行）"),
-    ]
+/// Whether the command invoker holds `MANAGE_GUILD`, used to let guild
+/// admins override `stop_requires_owner_or_admin` even if they didn't start
+/// the session themselves.
+async fn is_guild_admin(ctx: Context<'_>) -> bool {
+    ctx.author_member()
+        .await
+        .and_then(|member| member.permissions(ctx.serenity_context()).ok())
+        .map(|perms| perms.contains(Permissions::MANAGE_GUILD))
+        .unwrap_or(false)
 }
 
-/// Handle /analyze_start command
-pub async fn handle_start(
-    ctx: &Context,
-    command: &CommandInteraction,
-    session_manager: Arc<SessionManager>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let guild_id = command.guild_id.ok_or("Must be used in a guild")?;
-    
+/// ボイスチャットの分析を開始します
+#[poise::command(slash_command, guild_only)]
+pub async fn analyze_start(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Must be used in a guild")?;
+    let session_manager = ctx.data().session_manager.clone();
+
     // Get user's voice channel from guild cache
     let voice_channel_id = {
-        let guild = ctx.cache.guild(guild_id).ok_or("Guild not in cache")?;
+        let guild = ctx.guild().ok_or("Guild not in cache")?;
         guild
             .voice_states
-            .get(&command.user.id)
+            .get(&ctx.author().id)
             .and_then(|vs| vs.channel_id)
             .ok_or("ボイスチャットに参加してからコマンドを実行してください。")?
     };
 
     // Check if already recording
     if session_manager.get_session(guild_id).is_some() {
-        respond(ctx, command, "既に分析を実行中です。").await?;
+        ctx.say("既に分析を実行中です。").await?;
         return Ok(());
     }
 
-    // Defer response
-    command.defer(&ctx.http).await?;
+    ctx.defer().await?;
 
     // Get songbird manager
-    let manager = songbird::get(ctx).await.ok_or("Songbird not registered")?;
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .ok_or("Songbird not registered")?;
 
     // Join voice channel
     let call = manager.join(guild_id, voice_channel_id).await?;
 
     // Create session
-    let _session = session_manager.create_session(guild_id, command.channel_id, call).await?;
-    
+    let session = session_manager
+        .create_session(guild_id, ctx.channel_id(), voice_channel_id, ctx.author().id, call)
+        .await?;
+
+    // Seed display names for everyone already in the channel, so speaker
+    // attribution in reports shows real names instead of generic SSRC labels.
+    // Collected into an owned Vec first so the guild cache guard is dropped
+    // before the session `.write().await` below, instead of being held live
+    // across the await point.
+    let members_in_channel: Vec<(serenity::all::UserId, String)> = {
+        let guild = ctx.guild().ok_or("Guild not in cache")?;
+        guild
+            .voice_states
+            .iter()
+            .filter(|(_, vs)| vs.channel_id == Some(voice_channel_id))
+            .map(|(user_id, _)| {
+                let name = guild
+                    .members
+                    .get(user_id)
+                    .map(|m| m.display_name().to_string())
+                    .unwrap_or_else(|| user_id.to_string());
+                (*user_id, name)
+            })
+            .collect()
+    };
+    {
+        let session = session.write().await;
+        for (user_id, name) in members_in_channel {
+            session.register_user(user_id, name);
+        }
+    }
+
     // Start analysis loop
-    session_manager.start_analysis_loop(guild_id, ctx.http.clone());
+    session_manager.start_analysis_loop(guild_id, ctx.serenity_context().http.clone());
 
     // Get channel name for response
-    let channel_name = ctx.cache.channel(voice_channel_id)
+    let channel_name = ctx
+        .serenity_context()
+        .cache
+        .channel(voice_channel_id)
         .map(|c| c.name.clone())
         .unwrap_or_else(|| "Unknown".to_string());
 
-    let response = EditInteractionResponse::new()
-        .content(format!(
-            "{} の分析を開始しました。プライバシー保護のため、録音・分析が行われることを参加者に周知してください。",
-            channel_name
-        ));
-    command.edit_response(&ctx.http, response).await?;
+    ctx.say(format!(
+        "{} の分析を開始しました。プライバシー保護のため、録音・分析が行われることを参加者に周知してください。",
+        channel_name
+    ))
+    .await?;
 
     info!("Started recording in guild {} channel {}", guild_id, voice_channel_id);
     Ok(())
 }
 
-/// Handle /analyze_stop command
-pub async fn handle_stop(
-    ctx: &Context,
-    command: &CommandInteraction,
-    session_manager: Arc<SessionManager>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let guild_id = command.guild_id.ok_or("Must be used in a guild")?;
+/// 分析を終了し、ボイスチャットから退出します
+#[poise::command(slash_command, guild_only)]
+pub async fn analyze_stop(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Must be used in a guild")?;
+    let session_manager = ctx.data().session_manager.clone();
 
     // Check if recording
     if session_manager.get_session(guild_id).is_none() {
-        respond(ctx, command, "分析は実行されていません。").await?;
+        ctx.say("分析は実行されていません。").await?;
         return Ok(());
     }
 
-    respond(ctx, command, "🔄 最終レポートを作成して終了します。しばらくお待ちください...").await?;
+    let is_admin = is_guild_admin(ctx).await;
+
+    ctx.say("🔄 最終レポートを作成して終了します。しばらくお待ちください...").await?;
 
     // Cleanup session (runs final analysis)
-    session_manager.cleanup_session(guild_id, ctx.http.clone()).await?;
+    if let Err(e) = session_manager
+        .cleanup_session(guild_id, ctx.author().id, is_admin, ctx.serenity_context().http.clone())
+        .await
+    {
+        ctx.channel_id()
+            .say(&ctx.serenity_context().http, format!("⚠️ エラー: {}", e))
+            .await?;
+        return Ok(());
+    }
 
     // Leave voice channel
-    let manager = songbird::get(ctx).await.ok_or("Songbird not registered")?;
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .ok_or("Songbird not registered")?;
     let _ = manager.leave(guild_id).await;
 
-    let msg = CreateMessage::new().content("✅ 分析を終了しました。お疲れ様でした！");
-    command.channel_id.send_message(&ctx.http, msg).await?;
+    ctx.channel_id()
+        .say(&ctx.serenity_context().http, "✅ 分析を終了しました。お疲れ様でした！")
+        .await?;
 
     info!("Stopped recording in guild {}", guild_id);
     Ok(())
 }
 
-/// Handle /analyze_now command
-pub async fn handle_now(
-    ctx: &Context,
-    command: &CommandInteraction,
-    session_manager: Arc<SessionManager>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let guild_id = command.guild_id.ok_or("Must be used in a guild")?;
+/// すぐにレポートを作成します（分析間隔を待たずに実行）
+#[poise::command(slash_command, guild_only)]
+pub async fn analyze_now(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Must be used in a guild")?;
+    let session_manager = ctx.data().session_manager.clone();
 
     // Check if recording
     if session_manager.get_session(guild_id).is_none() {
-        respond(ctx, command, "分析は実行されていません。先に /analyze_start を実行してください。").await?;
+        ctx.say("分析は実行されていません。先に /analyze_start を実行してください。").await?;
         return Ok(());
     }
 
-    respond(ctx, command, "🔄 手動分析を開始しました...").await?;
+    let is_admin = is_guild_admin(ctx).await;
+
+    ctx.say("🔄 手動分析を開始しました...").await?;
 
     // Force analysis
-    if let Err(e) = session_manager.force_analysis(guild_id, ctx.http.clone()).await {
-        let msg = CreateMessage::new().content(format!("⚠️ エラー: {}", e));
-        command.channel_id.send_message(&ctx.http, msg).await?;
+    if let Err(e) = session_manager
+        .force_analysis(guild_id, ctx.author().id, is_admin, ctx.serenity_context().http.clone())
+        .await
+    {
+        ctx.channel_id()
+            .say(&ctx.serenity_context().http, format!("⚠️ エラー: {}", e))
+            .await?;
     }
 
     Ok(())
 }
-
-/// Helper to send a response
-async fn respond(
-    ctx: &Context,
-    command: &CommandInteraction,
-    content: &str,
-) -> Result<(), serenity::Error> {
-    command.create_response(&ctx.http, CreateInteractionResponse::Message(
-        CreateInteractionResponseMessage::new().content(content)
-    )).await
-}
@@ -0,0 +1,7 @@
+//! Slash commands, implemented as poise commands sharing the bot's `Data`
+//!
+//! See `crate::bot` for the `Context`/`Error`/`Data` type aliases threaded
+//! through every command here.
+
+pub mod analyze;
+pub mod settings;
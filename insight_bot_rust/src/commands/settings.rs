@@ -1,152 +1,191 @@
-//! Settings commands: /settings set_mode, /settings set_interval
+//! Settings commands: /settings set_mode, /settings set_interval, /settings set_model,
+//! /settings set_voice_feedback, /settings set_stop_restriction, /settings set_format
+//!
+//! Each subcommand argument is typed so poise parses and validates it before the
+//! command body ever runs (choice enums for fixed option sets, a ranged `u64` for
+//! the interval, and a native Discord boolean for the on/off toggle).
 
-use serenity::all::{
-    CommandInteraction, CommandOptionType, Context, CreateCommand,
-    CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
-};
-use std::sync::Arc;
+use crate::bot::{Context, Error};
+use crate::database::{AnalysisMode, RecordingFormat};
 use tracing::info;
 
-use crate::database::{AnalysisMode, Database};
-
-/// Register settings commands
-pub fn register() -> Vec<CreateCommand> {
-    vec![
-        CreateCommand::new("settings")
-            .description("Botの設定を変更します")
-            .add_option(
-                CreateCommandOption::new(
-                    CommandOptionType::SubCommand,
-                    "set_mode",
-                    "分析モードを変更します (debate / summary)",
-                )
-                .add_sub_option(
-                    CreateCommandOption::new(
-                        CommandOptionType::String,
-                        "mode",
-                        "分析モード",
-                    )
-                    .required(true)
-                    .add_string_choice("debate", "debate")
-                    .add_string_choice("summary", "summary"),
-                ),
-            )
-            .add_option(
-                CreateCommandOption::new(
-                    CommandOptionType::SubCommand,
-                    "set_interval",
-                    "分析間隔（秒）を変更します",
-                )
-                .add_sub_option(
-                    CreateCommandOption::new(
-                        CommandOptionType::Integer,
-                        "seconds",
-                        "間隔（秒）",
-                    )
-                    .required(true)
-                    .min_int_value(60)
-                    .max_int_value(3600),
-                ),
-            ),
-    ]
+/// 分析モードの選択肢
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum ModeChoice {
+    #[name = "debate"]
+    Debate,
+    #[name = "summary"]
+    Summary,
 }
 
-/// Handle /settings command
-pub async fn handle(
-    ctx: &Context,
-    command: &CommandInteraction,
-    db: Arc<Database>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let guild_id = command.guild_id.ok_or("Must be used in a guild")?;
-
-    let options = &command.data.options();
-    if options.is_empty() {
-        respond(ctx, command, "サブコマンドを指定してください。", true).await?;
-        return Ok(());
+impl ModeChoice {
+    fn as_analysis_mode(&self) -> AnalysisMode {
+        match self {
+            ModeChoice::Debate => AnalysisMode::Debate,
+            ModeChoice::Summary => AnalysisMode::Summary,
+        }
     }
+}
 
-    let subcommand = &options[0];
-    let subcommand_name: &str = &subcommand.name;
-    
-    match subcommand_name {
-        "set_mode" => {
-            // Get the mode value from subcommand options
-            if let serenity::all::ResolvedValue::SubCommand(sub_opts) = &subcommand.value {
-                if let Some(mode_opt) = sub_opts.first() {
-                    if let serenity::all::ResolvedValue::String(mode_str) = &mode_opt.value {
-                        if let Some(mode) = AnalysisMode::from_str(mode_str) {
-                            db.set_analysis_mode(guild_id.get(), mode)?;
-                            respond(
-                                ctx,
-                                command,
-                                &format!("✅ 分析モードを '{}' に変更しました。", mode.as_str()),
-                                false,
-                            ).await?;
-                            info!("Guild {} set mode to {}", guild_id, mode.as_str());
-                        } else {
-                            respond(
-                                ctx,
-                                command,
-                                "❌ モードは 'debate' または 'summary' を指定してください。",
-                                true,
-                            ).await?;
-                        }
-                    }
-                }
-            }
-        }
-        "set_interval" => {
-            // Get the seconds value from subcommand options
-            if let serenity::all::ResolvedValue::SubCommand(sub_opts) = &subcommand.value {
-                if let Some(sec_opt) = sub_opts.first() {
-                    if let serenity::all::ResolvedValue::Integer(seconds) = &sec_opt.value {
-                        let seconds = *seconds as u64;
-                        if seconds < 60 {
-                            respond(
-                                ctx,
-                                command,
-                                "❌ 間隔は最短60秒です。",
-                                true,
-                            ).await?;
-                        } else {
-                            db.set_recording_interval(guild_id.get(), seconds)?;
-                            respond(
-                                ctx,
-                                command,
-                                &format!(
-                                    "✅ 分析間隔を {}秒 ({:.1}分) に変更しました。",
-                                    seconds,
-                                    seconds as f64 / 60.0
-                                ),
-                                false,
-                            ).await?;
-                            info!("Guild {} set interval to {}s", guild_id, seconds);
-                        }
-                    }
-                }
-            }
+/// Geminiモデルの選択肢
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum ModelChoice {
+    #[name = "flash"]
+    Flash,
+    #[name = "pro"]
+    Pro,
+}
+
+impl ModelChoice {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ModelChoice::Flash => "flash",
+            ModelChoice::Pro => "pro",
         }
-        _ => {
-            respond(ctx, command, "不明なサブコマンドです。", true).await?;
+    }
+}
+
+/// 録音アーカイブの保存形式の選択肢
+///
+/// `flac`/`mp3` archival encoding isn't implemented yet (see
+/// `AudioProcessor::archive_opus_frames`), so they're left out here rather
+/// than offering a choice that always fails after claiming success.
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum FormatChoice {
+    #[name = "opus"]
+    Opus,
+    #[name = "wav"]
+    Wav,
+}
+
+impl FormatChoice {
+    fn as_recording_format(&self) -> RecordingFormat {
+        match self {
+            FormatChoice::Opus => RecordingFormat::Opus,
+            FormatChoice::Wav => RecordingFormat::Wav,
         }
     }
+}
+
+/// Botの設定を変更します
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("set_mode", "set_interval", "set_model", "set_voice_feedback", "set_stop_restriction", "set_format")
+)]
+pub async fn settings(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// 分析モードを変更します (debate / summary)
+#[poise::command(slash_command, guild_only, rename = "set_mode")]
+pub async fn set_mode(
+    ctx: Context<'_>,
+    #[description = "分析モード"] mode: ModeChoice,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Must be used in a guild")?;
+    let mode = mode.as_analysis_mode();
 
+    ctx.data().db.set_analysis_mode(guild_id.get(), mode).await?;
+    ctx.say(format!("✅ 分析モードを '{}' に変更しました。", mode.as_str())).await?;
+
+    info!("Guild {} set mode to {}", guild_id, mode.as_str());
     Ok(())
 }
 
-/// Helper to send a response
-async fn respond(
-    ctx: &Context,
-    command: &CommandInteraction,
-    content: &str,
-    ephemeral: bool,
-) -> Result<(), serenity::Error> {
-    command.create_response(
-        &ctx.http,
-        CreateInteractionResponse::Message(
-            CreateInteractionResponseMessage::new()
-                .content(content)
-                .ephemeral(ephemeral),
-        ),
-    ).await
+/// 分析間隔（秒）を変更します
+#[poise::command(slash_command, guild_only, rename = "set_interval")]
+pub async fn set_interval(
+    ctx: Context<'_>,
+    #[description = "間隔（秒）"]
+    #[min = 60]
+    #[max = 3600]
+    seconds: u64,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Must be used in a guild")?;
+
+    ctx.data().db.set_recording_interval(guild_id.get(), seconds).await?;
+    ctx.say(format!(
+        "✅ 分析間隔を {}秒 ({:.1}分) に変更しました。",
+        seconds,
+        seconds as f64 / 60.0
+    ))
+    .await?;
+
+    info!("Guild {} set interval to {}s", guild_id, seconds);
+    Ok(())
+}
+
+/// 分析に使用するGeminiモデルを変更します (flash / pro)
+#[poise::command(slash_command, guild_only, rename = "set_model")]
+pub async fn set_model(
+    ctx: Context<'_>,
+    #[description = "モデル"] model: ModelChoice,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Must be used in a guild")?;
+    let model = model.as_str();
+
+    ctx.data().db.set_gemini_model(guild_id.get(), model).await?;
+    ctx.say(format!("✅ 使用モデルを '{}' に変更しました。", model)).await?;
+
+    info!("Guild {} set model to {}", guild_id, model);
+    Ok(())
+}
+
+/// レポートをボイスチャットで読み上げるかを変更します
+#[poise::command(slash_command, guild_only, rename = "set_voice_feedback")]
+pub async fn set_voice_feedback(
+    ctx: Context<'_>,
+    #[description = "読み上げを有効にするか"] enabled: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Must be used in a guild")?;
+
+    ctx.data().db.set_voice_feedback(guild_id.get(), enabled).await?;
+    ctx.say(format!(
+        "✅ レポートの読み上げを{}にしました。",
+        if enabled { "オン" } else { "オフ" }
+    ))
+    .await?;
+
+    info!("Guild {} set voice_feedback to {}", guild_id, enabled);
+    Ok(())
+}
+
+/// /analyze_stop と /analyze_now をセッション開始者か管理者のみに制限するかを変更します
+#[poise::command(slash_command, guild_only, rename = "set_stop_restriction")]
+pub async fn set_stop_restriction(
+    ctx: Context<'_>,
+    #[description = "開始者・管理者のみに制限するか"] enabled: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Must be used in a guild")?;
+
+    ctx.data().db.set_stop_requires_owner_or_admin(guild_id.get(), enabled).await?;
+    ctx.say(format!(
+        "✅ 分析の終了・即時実行をセッション開始者と管理者のみに制限する設定を{}にしました。",
+        if enabled { "オン" } else { "オフ" }
+    ))
+    .await?;
+
+    info!("Guild {} set stop_requires_owner_or_admin to {}", guild_id, enabled);
+    Ok(())
+}
+
+/// 録音をアーカイブする際の保存形式を変更します (opus / wav)
+///
+/// Analysis always uses the downsized 16kHz mono WAV upload copy regardless
+/// of this setting;
+/// it only controls what gets re-encoded for long-term archival afterward.
+#[poise::command(slash_command, guild_only, rename = "set_format")]
+pub async fn set_format(
+    ctx: Context<'_>,
+    #[description = "アーカイブ形式"] format: FormatChoice,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Must be used in a guild")?;
+    let format = format.as_recording_format();
+
+    ctx.data().db.set_recording_format(guild_id.get(), format).await?;
+    ctx.say(format!("✅ 録音アーカイブの形式を '{}' に変更しました。", format.as_str())).await?;
+
+    info!("Guild {} set recording_format to {}", guild_id, format.as_str());
+    Ok(())
 }
@@ -14,6 +14,13 @@ pub enum ConfigError {
     InvalidValue(String, String),
 }
 
+/// Which `Store` backend to use for guild settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbType {
+    Sqlite,
+    Postgres,
+}
+
 /// Application configuration
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -31,6 +38,16 @@ pub struct Config {
     pub temp_audio_dir: PathBuf,
     /// Default recording interval in seconds
     pub default_recording_interval: u64,
+    /// Prometheus Pushgateway URL (metrics subsystem is disabled if unset)
+    pub pushgateway_url: Option<String>,
+    /// Interval between metrics pushes, in seconds
+    pub metrics_push_interval: u64,
+    /// Which `Store` backend to use (default: sqlite)
+    pub db_type: DbType,
+    /// Postgres connection string, required when `db_type` is `Postgres`
+    pub database_url: Option<String>,
+    /// HTTP TTS endpoint used to speak reports back into voice channels (optional)
+    pub tts_endpoint: Option<String>,
 }
 
 impl Config {
@@ -63,6 +80,30 @@ impl Config {
             .and_then(|s| s.parse().ok())
             .unwrap_or(300);
 
+        let pushgateway_url = env::var("PUSHGATEWAY_URL")
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        let metrics_push_interval = env::var("METRICS_PUSH_INTERVAL")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(15);
+
+        let db_type = match env::var("DB_TYPE").ok().as_deref() {
+            None | Some("sqlite") => DbType::Sqlite,
+            Some("postgres") => DbType::Postgres,
+            Some(other) => {
+                return Err(ConfigError::InvalidValue("DB_TYPE".to_string(), other.to_string()))
+            }
+        };
+
+        let database_url = env::var("DATABASE_URL").ok().filter(|s| !s.is_empty());
+        if db_type == DbType::Postgres && database_url.is_none() {
+            return Err(ConfigError::MissingEnvVar("DATABASE_URL".to_string()));
+        }
+
+        let tts_endpoint = env::var("TTS_ENDPOINT").ok().filter(|s| !s.is_empty());
+
         Ok(Self {
             discord_token,
             gemini_api_key,
@@ -71,6 +112,11 @@ impl Config {
             channels: 2,
             temp_audio_dir,
             default_recording_interval,
+            pushgateway_url,
+            metrics_push_interval,
+            db_type,
+            database_url,
+            tts_endpoint,
         })
     }
 }
@@ -0,0 +1,225 @@
+//! Database management for guild settings
+//!
+//! Storage is pluggable behind the [`Store`] trait: a zero-config SQLite
+//! backend (the default) and a pooled Postgres backend for multi-instance
+//! deployments where per-guild settings and context history need to survive
+//! restarts and be shared across bot instances.
+
+mod postgres;
+mod sqlite;
+
+pub use postgres::PostgresStore;
+pub use sqlite::SqliteStore;
+
+use crate::config::{Config, DbType};
+use async_trait::async_trait;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DatabaseError {
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Postgres error: {0}")]
+    Postgres(#[from] tokio_postgres::Error),
+    #[error("Postgres pool error: {0}")]
+    Pool(#[from] bb8::RunError<tokio_postgres::Error>),
+    #[error("Lock error")]
+    LockError,
+    #[error("DATABASE_URL is required when DB_TYPE=postgres")]
+    MissingDatabaseUrl,
+}
+
+/// Analysis mode for the bot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalysisMode {
+    Debate,
+    Summary,
+}
+
+impl AnalysisMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnalysisMode::Debate => "debate",
+            AnalysisMode::Summary => "summary",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "debate" => Some(AnalysisMode::Debate),
+            "summary" => Some(AnalysisMode::Summary),
+            _ => None,
+        }
+    }
+}
+
+impl Default for AnalysisMode {
+    fn default() -> Self {
+        AnalysisMode::Debate
+    }
+}
+
+/// Archival re-encoding format for saved recordings, selectable per guild.
+/// Analysis always consumes the downsized 16kHz mono WAV upload copy
+/// regardless of this setting; it only controls what (if anything) gets
+/// re-encoded for long-term storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// Keep only the OGG-Opus file muxed from the recorded frames (default)
+    Opus,
+    Wav,
+    Flac,
+    Mp3,
+}
+
+impl RecordingFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordingFormat::Opus => "opus",
+            RecordingFormat::Wav => "wav",
+            RecordingFormat::Flac => "flac",
+            RecordingFormat::Mp3 => "mp3",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "opus" => Some(RecordingFormat::Opus),
+            "wav" => Some(RecordingFormat::Wav),
+            "flac" => Some(RecordingFormat::Flac),
+            "mp3" => Some(RecordingFormat::Mp3),
+            _ => None,
+        }
+    }
+}
+
+impl Default for RecordingFormat {
+    fn default() -> Self {
+        RecordingFormat::Opus
+    }
+}
+
+/// Guild-specific settings
+#[derive(Debug, Clone)]
+pub struct GuildSettings {
+    pub guild_id: u64,
+    pub api_key: Option<String>,
+    pub analysis_mode: AnalysisMode,
+    pub recording_interval: u64,
+    /// Whether to speak generated reports back into the voice channel via TTS
+    pub voice_feedback_enabled: bool,
+    /// Selected Gemini model, as the short label used in `/settings set_model` ("flash" / "pro")
+    pub gemini_model: String,
+    /// Whether `/analyze_stop` and `/analyze_now` are restricted to the
+    /// session owner or a member with `MANAGE_GUILD`
+    pub stop_requires_owner_or_admin: bool,
+    /// Archival format recordings are re-encoded into after analysis
+    pub recording_format: RecordingFormat,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        Self {
+            guild_id: 0,
+            api_key: None,
+            analysis_mode: AnalysisMode::Debate,
+            recording_interval: 300,
+            voice_feedback_enabled: false,
+            gemini_model: "flash".to_string(),
+            stop_requires_owner_or_admin: false,
+            recording_format: RecordingFormat::Opus,
+        }
+    }
+}
+
+/// A recording session surviving long enough to need restoring after a
+/// restart: enough to rejoin the voice channel and carry analysis context
+/// forward without starting the conversation's history over from nothing.
+#[derive(Debug, Clone)]
+pub struct PersistedSession {
+    pub guild_id: u64,
+    pub text_channel_id: u64,
+    pub voice_channel_id: u64,
+    pub owner: u64,
+    pub session_timestamp: u64,
+    pub last_context: String,
+}
+
+/// A per-guild settings store, implemented by each supported backend.
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Get settings for a guild (returns defaults if not found)
+    async fn get_guild_settings(&self, guild_id: u64) -> Result<GuildSettings, DatabaseError>;
+
+    /// Update a specific setting for a guild
+    async fn update_guild_setting(
+        &self,
+        guild_id: u64,
+        key: &str,
+        value: &str,
+    ) -> Result<(), DatabaseError>;
+
+    /// Update analysis mode for a guild
+    async fn set_analysis_mode(&self, guild_id: u64, mode: AnalysisMode) -> Result<(), DatabaseError> {
+        self.update_guild_setting(guild_id, "analysis_mode", mode.as_str()).await
+    }
+
+    /// Update recording interval for a guild
+    async fn set_recording_interval(&self, guild_id: u64, interval: u64) -> Result<(), DatabaseError> {
+        self.update_guild_setting(guild_id, "recording_interval", &interval.to_string()).await
+    }
+
+    /// Toggle whether reports are spoken back into the voice channel
+    async fn set_voice_feedback(&self, guild_id: u64, enabled: bool) -> Result<(), DatabaseError> {
+        self.update_guild_setting(guild_id, "voice_feedback_enabled", if enabled { "1" } else { "0" }).await
+    }
+
+    /// Update the selected Gemini model ("flash" or "pro") for a guild
+    async fn set_gemini_model(&self, guild_id: u64, model: &str) -> Result<(), DatabaseError> {
+        self.update_guild_setting(guild_id, "gemini_model", model).await
+    }
+
+    /// Toggle whether `/analyze_stop` and `/analyze_now` require the caller
+    /// to be the session owner or hold `MANAGE_GUILD`
+    async fn set_stop_requires_owner_or_admin(&self, guild_id: u64, enabled: bool) -> Result<(), DatabaseError> {
+        self.update_guild_setting(guild_id, "stop_requires_owner_or_admin", if enabled { "1" } else { "0" }).await
+    }
+
+    /// Update the archival re-encoding format for a guild's recordings
+    async fn set_recording_format(&self, guild_id: u64, format: RecordingFormat) -> Result<(), DatabaseError> {
+        self.update_guild_setting(guild_id, "recording_format", format.as_str()).await
+    }
+
+    /// Record a newly-started session so it can be resumed if the bot
+    /// restarts before it's stopped. Overwrites any existing row for the guild.
+    async fn save_session(&self, session: &PersistedSession) -> Result<(), DatabaseError>;
+
+    /// Update the saved analysis context for an active session, so a resume
+    /// picks up the conversation where it left off rather than from nothing
+    async fn update_session_context(&self, guild_id: u64, last_context: &str) -> Result<(), DatabaseError>;
+
+    /// Forget a session once it's stopped cleanly; nothing left to resume
+    async fn delete_session(&self, guild_id: u64) -> Result<(), DatabaseError>;
+
+    /// All sessions that were active when the bot last shut down (cleanly or not)
+    async fn list_sessions(&self) -> Result<Vec<PersistedSession>, DatabaseError>;
+}
+
+/// Trait-object alias so the rest of the crate can keep writing `Arc<Database>`
+/// regardless of which backend was selected at startup.
+pub type Database = dyn Store;
+
+/// Open the storage backend selected by `Config::db_type`.
+pub async fn open(config: &Config) -> Result<Arc<Database>, DatabaseError> {
+    match config.db_type {
+        DbType::Sqlite => Ok(Arc::new(SqliteStore::open("bot_settings.db")?)),
+        DbType::Postgres => {
+            let url = config
+                .database_url
+                .clone()
+                .ok_or(DatabaseError::MissingDatabaseUrl)?;
+            Ok(Arc::new(PostgresStore::connect(&url).await?))
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Pooled Postgres-backed `Store` implementation
+//!
+//! Intended for multi-instance/containerized deployments where the
+//! embedded SQLite file is a bottleneck and per-guild settings need to be
+//! shared across bot instances. The pool is created once at startup.
+
+use super::{AnalysisMode, DatabaseError, GuildSettings, PersistedSession, RecordingFormat, Store};
+use async_trait::async_trait;
+use bb8::Pool;
+use bb8_postgres::PostgresConnectionManager;
+use tokio_postgres::NoTls;
+use tracing::info;
+
+pub struct PostgresStore {
+    pool: Pool<PostgresConnectionManager<NoTls>>,
+}
+
+impl PostgresStore {
+    /// Connect to Postgres and build the connection pool.
+    pub async fn connect(database_url: &str) -> Result<Self, DatabaseError> {
+        let manager = PostgresConnectionManager::new_from_stringlike(database_url, NoTls)?;
+        let pool = Pool::builder().build(manager).await?;
+
+        let store = Self { pool };
+        store.init().await?;
+        info!("Connected to Postgres guild settings store");
+        Ok(store)
+    }
+
+    async fn init(&self) -> Result<(), DatabaseError> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS guild_settings (
+                guild_id BIGINT PRIMARY KEY,
+                api_key TEXT,
+                analysis_mode TEXT NOT NULL DEFAULT 'debate',
+                recording_interval BIGINT NOT NULL DEFAULT 300,
+                voice_feedback_enabled BOOLEAN NOT NULL DEFAULT false,
+                gemini_model TEXT NOT NULL DEFAULT 'flash',
+                stop_requires_owner_or_admin BOOLEAN NOT NULL DEFAULT false,
+                recording_format TEXT NOT NULL DEFAULT 'opus'
+            )",
+            &[],
+        ).await?;
+        // Deployments that already have a guild_settings table from before
+        // these columns existed need them added in place.
+        conn.execute(
+            "ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS voice_feedback_enabled BOOLEAN NOT NULL DEFAULT false",
+            &[],
+        ).await?;
+        conn.execute(
+            "ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS gemini_model TEXT NOT NULL DEFAULT 'flash'",
+            &[],
+        ).await?;
+        conn.execute(
+            "ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS stop_requires_owner_or_admin BOOLEAN NOT NULL DEFAULT false",
+            &[],
+        ).await?;
+        conn.execute(
+            "ALTER TABLE guild_settings ADD COLUMN IF NOT EXISTS recording_format TEXT NOT NULL DEFAULT 'opus'",
+            &[],
+        ).await?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS active_sessions (
+                guild_id BIGINT PRIMARY KEY,
+                text_channel_id BIGINT NOT NULL,
+                voice_channel_id BIGINT NOT NULL,
+                owner BIGINT NOT NULL,
+                session_timestamp BIGINT NOT NULL,
+                last_context TEXT NOT NULL DEFAULT ''
+            )",
+            &[],
+        ).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Store for PostgresStore {
+    async fn get_guild_settings(&self, guild_id: u64) -> Result<GuildSettings, DatabaseError> {
+        let conn = self.pool.get().await?;
+        let row = conn
+            .query_opt(
+                "SELECT guild_id, api_key, analysis_mode, recording_interval, voice_feedback_enabled, gemini_model, stop_requires_owner_or_admin, recording_format
+                 FROM guild_settings WHERE guild_id = $1",
+                &[&(guild_id as i64)],
+            )
+            .await?;
+
+        match row {
+            Some(row) => {
+                let mode_str: String = row.get(2);
+                let interval: i64 = row.get(3);
+                let format_str: String = row.get(7);
+                Ok(GuildSettings {
+                    guild_id: row.get::<_, i64>(0) as u64,
+                    api_key: row.get(1),
+                    analysis_mode: AnalysisMode::from_str(&mode_str).unwrap_or_default(),
+                    recording_interval: interval as u64,
+                    voice_feedback_enabled: row.get(4),
+                    gemini_model: row.get(5),
+                    stop_requires_owner_or_admin: row.get(6),
+                    recording_format: RecordingFormat::from_str(&format_str).unwrap_or_default(),
+                })
+            }
+            None => Ok(GuildSettings {
+                guild_id,
+                ..Default::default()
+            }),
+        }
+    }
+
+    async fn update_guild_setting(
+        &self,
+        guild_id: u64,
+        key: &str,
+        value: &str,
+    ) -> Result<(), DatabaseError> {
+        let mut settings = self.get_guild_settings(guild_id).await?;
+
+        match key {
+            "api_key" => settings.api_key = Some(value.to_string()),
+            "analysis_mode" => {
+                if let Some(mode) = AnalysisMode::from_str(value) {
+                    settings.analysis_mode = mode;
+                }
+            }
+            "recording_interval" => {
+                if let Ok(interval) = value.parse() {
+                    settings.recording_interval = interval;
+                }
+            }
+            "voice_feedback_enabled" => {
+                settings.voice_feedback_enabled = value == "1";
+            }
+            "gemini_model" => {
+                settings.gemini_model = value.to_string();
+            }
+            "stop_requires_owner_or_admin" => {
+                settings.stop_requires_owner_or_admin = value == "1";
+            }
+            "recording_format" => {
+                if let Some(format) = RecordingFormat::from_str(value) {
+                    settings.recording_format = format;
+                }
+            }
+            _ => {}
+        }
+
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "INSERT INTO guild_settings (guild_id, api_key, analysis_mode, recording_interval, voice_feedback_enabled, gemini_model, stop_requires_owner_or_admin, recording_format)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+             ON CONFLICT (guild_id) DO UPDATE SET
+                api_key = EXCLUDED.api_key,
+                analysis_mode = EXCLUDED.analysis_mode,
+                recording_interval = EXCLUDED.recording_interval,
+                voice_feedback_enabled = EXCLUDED.voice_feedback_enabled,
+                gemini_model = EXCLUDED.gemini_model,
+                stop_requires_owner_or_admin = EXCLUDED.stop_requires_owner_or_admin,
+                recording_format = EXCLUDED.recording_format",
+            &[
+                &(guild_id as i64),
+                &settings.api_key,
+                &settings.analysis_mode.as_str(),
+                &(settings.recording_interval as i64),
+                &settings.voice_feedback_enabled,
+                &settings.gemini_model,
+                &settings.stop_requires_owner_or_admin,
+                &settings.recording_format.as_str(),
+            ],
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn save_session(&self, session: &PersistedSession) -> Result<(), DatabaseError> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "INSERT INTO active_sessions
+             (guild_id, text_channel_id, voice_channel_id, owner, session_timestamp, last_context)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT (guild_id) DO UPDATE SET
+                text_channel_id = EXCLUDED.text_channel_id,
+                voice_channel_id = EXCLUDED.voice_channel_id,
+                owner = EXCLUDED.owner,
+                session_timestamp = EXCLUDED.session_timestamp,
+                last_context = EXCLUDED.last_context",
+            &[
+                &(session.guild_id as i64),
+                &(session.text_channel_id as i64),
+                &(session.voice_channel_id as i64),
+                &(session.owner as i64),
+                &(session.session_timestamp as i64),
+                &session.last_context,
+            ],
+        ).await?;
+        Ok(())
+    }
+
+    async fn update_session_context(&self, guild_id: u64, last_context: &str) -> Result<(), DatabaseError> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "UPDATE active_sessions SET last_context = $1 WHERE guild_id = $2",
+            &[&last_context, &(guild_id as i64)],
+        ).await?;
+        Ok(())
+    }
+
+    async fn delete_session(&self, guild_id: u64) -> Result<(), DatabaseError> {
+        let conn = self.pool.get().await?;
+        conn.execute(
+            "DELETE FROM active_sessions WHERE guild_id = $1",
+            &[&(guild_id as i64)],
+        ).await?;
+        Ok(())
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<PersistedSession>, DatabaseError> {
+        let conn = self.pool.get().await?;
+        let rows = conn
+            .query(
+                "SELECT guild_id, text_channel_id, voice_channel_id, owner, session_timestamp, last_context FROM active_sessions",
+                &[],
+            )
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| PersistedSession {
+                guild_id: row.get::<_, i64>(0) as u64,
+                text_channel_id: row.get::<_, i64>(1) as u64,
+                voice_channel_id: row.get::<_, i64>(2) as u64,
+                owner: row.get::<_, i64>(3) as u64,
+                session_timestamp: row.get::<_, i64>(4) as u64,
+                last_context: row.get(5),
+            })
+            .collect())
+    }
+}
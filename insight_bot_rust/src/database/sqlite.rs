@@ -0,0 +1,310 @@
+//! SQLite-backed `Store` implementation
+//!
+//! The zero-config default: a single-file embedded database, unchanged in
+//! behavior from the original hardcoded backend.
+
+use super::{AnalysisMode, DatabaseError, GuildSettings, PersistedSession, RecordingFormat, Store};
+use async_trait::async_trait;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::info;
+
+/// A single migration step: either plain SQL, or an `ALTER TABLE ADD COLUMN`
+/// that must be skipped when the column already exists. The latter is needed
+/// for columns that v1's `CREATE TABLE` now bakes in (added via direct
+/// schema edits before this migration runner existed) but which a real
+/// pre-migration-runner database won't have — a fresh install already has
+/// the column by the time such a step runs, while an old one doesn't.
+enum Migration {
+    Sql(&'static str),
+    AddColumnIfMissing {
+        table: &'static str,
+        column: &'static str,
+        ddl: &'static str,
+    },
+}
+
+/// Ordered schema migrations, applied based on `PRAGMA user_version`. Each
+/// entry's 1-based position in this slice is its version: append new
+/// migrations here rather than editing earlier ones, so existing
+/// `bot_settings.db` files upgrade in place instead of needing manual surgery.
+const MIGRATIONS: &[Migration] = &[
+    // v1: initial guild_settings table
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS guild_settings (
+        guild_id INTEGER PRIMARY KEY,
+        api_key TEXT,
+        analysis_mode TEXT DEFAULT 'debate',
+        recording_interval INTEGER DEFAULT 300,
+        voice_feedback_enabled INTEGER DEFAULT 0,
+        gemini_model TEXT DEFAULT 'flash'
+    )",
+    ),
+    // v2: index for the lookups get_guild_settings/update_guild_setting already do
+    Migration::Sql("CREATE INDEX IF NOT EXISTS idx_guild_settings_guild_id ON guild_settings(guild_id)"),
+    // v3: restrict /analyze_stop and /analyze_now to the session owner or an admin
+    Migration::Sql("ALTER TABLE guild_settings ADD COLUMN stop_requires_owner_or_admin INTEGER DEFAULT 0"),
+    // v4: per-guild archival re-encoding format for saved recordings
+    Migration::Sql("ALTER TABLE guild_settings ADD COLUMN recording_format TEXT DEFAULT 'opus'"),
+    // v5: active sessions, so a restart can rejoin and resume rather than
+    // silently orphaning an in-progress recording
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS active_sessions (
+        guild_id INTEGER PRIMARY KEY,
+        text_channel_id INTEGER NOT NULL,
+        voice_channel_id INTEGER NOT NULL,
+        owner INTEGER NOT NULL,
+        session_timestamp INTEGER NOT NULL,
+        last_context TEXT NOT NULL DEFAULT ''
+    )",
+    ),
+    // v6/v7: `voice_feedback_enabled`/`gemini_model` for databases that
+    // predate the migration runner — v1 above already creates them on a
+    // fresh install, so these are no-ops there and only backfill real
+    // databases that were stuck on the original 4-column schema
+    Migration::AddColumnIfMissing {
+        table: "guild_settings",
+        column: "voice_feedback_enabled",
+        ddl: "ALTER TABLE guild_settings ADD COLUMN voice_feedback_enabled INTEGER DEFAULT 0",
+    },
+    Migration::AddColumnIfMissing {
+        table: "guild_settings",
+        column: "gemini_model",
+        ddl: "ALTER TABLE guild_settings ADD COLUMN gemini_model TEXT DEFAULT 'flash'",
+    },
+];
+
+/// SQLite connection wrapper
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Open or create database at the given path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let conn = Connection::open(path)?;
+        let store = Self {
+            conn: Mutex::new(conn),
+        };
+        store.init()?;
+        Ok(store)
+    }
+
+    /// Whether `table` already has a column named `column`, so an
+    /// `AddColumnIfMissing` migration can skip itself instead of failing
+    /// with "duplicate column name" on a database that already has it.
+    fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool, DatabaseError> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let exists = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .filter_map(Result::ok)
+            .any(|name| name == column);
+        Ok(exists)
+    }
+
+    /// Run any migrations newer than the database's current `user_version`
+    fn init(&self) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().map_err(|_| DatabaseError::LockError)?;
+
+        let current_version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            let version = (index + 1) as i64;
+            if version <= current_version {
+                continue;
+            }
+
+            let tx = conn.unchecked_transaction()?;
+            match migration {
+                Migration::Sql(sql) => {
+                    tx.execute(sql, [])?;
+                }
+                Migration::AddColumnIfMissing { table, column, ddl } => {
+                    if !Self::column_exists(&tx, table, column)? {
+                        tx.execute(ddl, [])?;
+                    }
+                }
+            }
+            tx.pragma_update(None, "user_version", version)?;
+            tx.commit()?;
+
+            info!("Applied guild_settings migration v{}", version);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn get_guild_settings(&self, guild_id: u64) -> Result<GuildSettings, DatabaseError> {
+        let conn = self.conn.lock().map_err(|_| DatabaseError::LockError)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT guild_id, api_key, analysis_mode, recording_interval, voice_feedback_enabled, gemini_model, stop_requires_owner_or_admin, recording_format
+             FROM guild_settings WHERE guild_id = ?"
+        )?;
+
+        let result = stmt.query_row([guild_id], |row| {
+            let mode_str: String = row.get(2)?;
+            let voice_feedback: i64 = row.get(4)?;
+            let stop_requires_owner_or_admin: i64 = row.get(6)?;
+            let format_str: String = row.get(7)?;
+            Ok(GuildSettings {
+                guild_id: row.get(0)?,
+                api_key: row.get(1)?,
+                analysis_mode: AnalysisMode::from_str(&mode_str).unwrap_or_default(),
+                recording_interval: row.get(3)?,
+                voice_feedback_enabled: voice_feedback != 0,
+                gemini_model: row.get(5)?,
+                stop_requires_owner_or_admin: stop_requires_owner_or_admin != 0,
+                recording_format: RecordingFormat::from_str(&format_str).unwrap_or_default(),
+            })
+        });
+
+        match result {
+            Ok(settings) => Ok(settings),
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                Ok(GuildSettings {
+                    guild_id,
+                    ..Default::default()
+                })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn update_guild_setting(
+        &self,
+        guild_id: u64,
+        key: &str,
+        value: &str,
+    ) -> Result<(), DatabaseError> {
+        let mut settings = self.get_guild_settings(guild_id).await?;
+
+        match key {
+            "api_key" => settings.api_key = Some(value.to_string()),
+            "analysis_mode" => {
+                if let Some(mode) = AnalysisMode::from_str(value) {
+                    settings.analysis_mode = mode;
+                }
+            }
+            "recording_interval" => {
+                if let Ok(interval) = value.parse() {
+                    settings.recording_interval = interval;
+                }
+            }
+            "voice_feedback_enabled" => {
+                settings.voice_feedback_enabled = value == "1";
+            }
+            "gemini_model" => {
+                settings.gemini_model = value.to_string();
+            }
+            "stop_requires_owner_or_admin" => {
+                settings.stop_requires_owner_or_admin = value == "1";
+            }
+            "recording_format" => {
+                if let Some(format) = RecordingFormat::from_str(value) {
+                    settings.recording_format = format;
+                }
+            }
+            _ => {}
+        }
+
+        let conn = self.conn.lock().map_err(|_| DatabaseError::LockError)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO guild_settings
+             (guild_id, api_key, analysis_mode, recording_interval, voice_feedback_enabled, gemini_model, stop_requires_owner_or_admin, recording_format)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                guild_id,
+                settings.api_key,
+                settings.analysis_mode.as_str(),
+                settings.recording_interval,
+                settings.voice_feedback_enabled as i64,
+                settings.gemini_model,
+                settings.stop_requires_owner_or_admin as i64,
+                settings.recording_format.as_str(),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    async fn save_session(&self, session: &PersistedSession) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().map_err(|_| DatabaseError::LockError)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO active_sessions
+             (guild_id, text_channel_id, voice_channel_id, owner, session_timestamp, last_context)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                session.guild_id,
+                session.text_channel_id,
+                session.voice_channel_id,
+                session.owner,
+                session.session_timestamp,
+                session.last_context,
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn update_session_context(&self, guild_id: u64, last_context: &str) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().map_err(|_| DatabaseError::LockError)?;
+        conn.execute(
+            "UPDATE active_sessions SET last_context = ? WHERE guild_id = ?",
+            params![last_context, guild_id],
+        )?;
+        Ok(())
+    }
+
+    async fn delete_session(&self, guild_id: u64) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().map_err(|_| DatabaseError::LockError)?;
+        conn.execute("DELETE FROM active_sessions WHERE guild_id = ?", params![guild_id])?;
+        Ok(())
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<PersistedSession>, DatabaseError> {
+        let conn = self.conn.lock().map_err(|_| DatabaseError::LockError)?;
+        let mut stmt = conn.prepare(
+            "SELECT guild_id, text_channel_id, voice_channel_id, owner, session_timestamp, last_context FROM active_sessions"
+        )?;
+
+        let sessions = stmt
+            .query_map([], |row| {
+                Ok(PersistedSession {
+                    guild_id: row.get(0)?,
+                    text_channel_id: row.get(1)?,
+                    voice_channel_id: row.get(2)?,
+                    owner: row.get(3)?,
+                    session_timestamp: row.get(4)?,
+                    last_context: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(sessions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_database_operations() {
+        let db = SqliteStore::open(":memory:").unwrap();
+
+        // Test default settings
+        let settings = db.get_guild_settings(12345).await.unwrap();
+        assert_eq!(settings.guild_id, 12345);
+        assert_eq!(settings.analysis_mode, AnalysisMode::Debate);
+        assert_eq!(settings.recording_interval, 300);
+
+        // Test update
+        db.set_analysis_mode(12345, AnalysisMode::Summary).await.unwrap();
+        let settings = db.get_guild_settings(12345).await.unwrap();
+        assert_eq!(settings.analysis_mode, AnalysisMode::Summary);
+    }
+}
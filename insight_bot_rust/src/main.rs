@@ -9,7 +9,9 @@ mod bot;
 mod commands;
 mod config;
 mod database;
+mod metrics;
 mod session;
+mod tts;
 
 use config::Config;
 use tracing::{error, info};
@@ -47,6 +49,14 @@ async fn main() {
         std::process::exit(1);
     }
 
+    // Start pushing operational metrics to the Pushgateway (no-op if unconfigured)
+    metrics::spawn_pusher(
+        config.pushgateway_url.clone(),
+        "insight_bot".to_string(),
+        "default".to_string(),
+        std::time::Duration::from_secs(config.metrics_push_interval),
+    );
+
     // Run the bot
     if let Err(e) = bot::run(config).await {
         error!("Bot error: {}", e);
@@ -0,0 +1,123 @@
+//! Prometheus metrics for analysis and Gemini API observability
+//!
+//! Tracks operational counters/histograms in-process and periodically pushes
+//! them to a Prometheus Pushgateway. When `PUSHGATEWAY_URL` is not configured
+//! the whole subsystem is a no-op.
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    HistogramVec, IntCounter, IntCounterVec, Registry, TextEncoder, register_histogram_vec_with_registry,
+    register_int_counter_vec_with_registry, register_int_counter_with_registry,
+};
+use reqwest::Client;
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+/// Global metrics registry, lazily initialized on first use.
+pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+/// Count of analyses run, labeled by guild and `AnalysisMode`.
+pub static ANALYSES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec_with_registry!(
+        "insight_bot_analyses_total",
+        "Number of analyses performed, labeled by guild and mode",
+        &["guild_id", "mode"],
+        REGISTRY
+    )
+    .expect("failed to register insight_bot_analyses_total")
+});
+
+/// End-to-end Gemini `generateContent` latency, in seconds.
+pub static GEMINI_GENERATE_LATENCY_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec_with_registry!(
+        "insight_bot_gemini_generate_latency_seconds",
+        "Latency of Gemini generateContent calls",
+        &["model"],
+        REGISTRY
+    )
+    .expect("failed to register insight_bot_gemini_generate_latency_seconds")
+});
+
+/// Per-file upload sizes, in bytes.
+pub static UPLOAD_FILE_SIZE_BYTES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec_with_registry!(
+        "insight_bot_upload_file_size_bytes",
+        "Size in bytes of files uploaded to the Gemini File API",
+        &["mime_type"],
+        REGISTRY
+    )
+    .expect("failed to register insight_bot_upload_file_size_bytes")
+});
+
+/// Count of `wait_for_file_active` timeouts.
+pub static FILE_ACTIVE_TIMEOUTS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter_with_registry!(
+        "insight_bot_file_active_timeouts_total",
+        "Number of times wait_for_file_active timed out",
+        REGISTRY
+    )
+    .expect("failed to register insight_bot_file_active_timeouts_total")
+});
+
+/// Count of `AnalyzerError::RateLimitExceeded` hits.
+pub static RATE_LIMIT_HITS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter_with_registry!(
+        "insight_bot_rate_limit_hits_total",
+        "Number of times the Gemini API returned a rate limit error",
+        REGISTRY
+    )
+    .expect("failed to register insight_bot_rate_limit_hits_total")
+});
+
+/// Force-initialize all metrics so they show up in the first push even
+/// before any activity has occurred.
+fn touch_all() {
+    Lazy::force(&ANALYSES_TOTAL);
+    Lazy::force(&GEMINI_GENERATE_LATENCY_SECONDS);
+    Lazy::force(&UPLOAD_FILE_SIZE_BYTES);
+    Lazy::force(&FILE_ACTIVE_TIMEOUTS_TOTAL);
+    Lazy::force(&RATE_LIMIT_HITS_TOTAL);
+}
+
+/// Spawn a background task that periodically pushes the registry to a
+/// Prometheus Pushgateway. Returns immediately (and spawns nothing) if
+/// `pushgateway_url` is `None`.
+pub fn spawn_pusher(pushgateway_url: Option<String>, job: String, instance: String, interval: Duration) {
+    let Some(base_url) = pushgateway_url else {
+        debug!("PUSHGATEWAY_URL not set, metrics push disabled");
+        return;
+    };
+
+    touch_all();
+
+    let url = format!("{}/metrics/job/{}/instance/{}", base_url.trim_end_matches('/'), job, instance);
+    let client = Client::new();
+
+    tokio::spawn(async move {
+        let encoder = TextEncoder::new();
+        loop {
+            tokio::time::sleep(interval).await;
+
+            let metric_families = REGISTRY.gather();
+            let mut buffer = String::new();
+            if let Err(e) = encoder.encode_utf8(&metric_families, &mut buffer) {
+                error!("Failed to encode metrics: {}", e);
+                continue;
+            }
+
+            match client.post(&url).body(buffer).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    debug!("Pushed metrics to {}", url);
+                }
+                Ok(resp) => {
+                    error!("Pushgateway returned {} for {}", resp.status(), url);
+                }
+                Err(e) => {
+                    error!("Failed to push metrics to {}: {}", url, e);
+                }
+            }
+        }
+    });
+
+    info!("Metrics push subsystem started (interval: {:?})", interval);
+}
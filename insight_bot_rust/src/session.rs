@@ -5,24 +5,34 @@
 use crate::analyzer::Analyzer;
 use crate::audio::{AudioProcessor, UserRecorder};
 use crate::config::Config;
-use crate::database::{AnalysisMode, Database, GuildSettings};
+use crate::database::{AnalysisMode, Database, GuildSettings, PersistedSession};
+use crate::tts::TtsSynthesizer;
 use dashmap::DashMap;
 use serenity::all::{ChannelId, CreateMessage, CreateThread, GuildId, Http, UserId};
 use songbird::Call;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 use tokio::time::Duration;
 use tracing::{debug, error, info, warn};
 
+/// How often a session's buffered audio is checkpointed to disk, so a crash
+/// loses at most this much unanalyzed audio instead of the whole buffer.
+const CHECKPOINT_INTERVAL_SECS: u64 = 30;
+
 /// A recording session for a single guild
 pub struct GuildSession {
     /// Guild ID
     pub guild_id: GuildId,
     /// Text channel to post reports
     pub text_channel_id: ChannelId,
+    /// Voice channel this session is recording, needed to rejoin on resume
+    pub voice_channel_id: ChannelId,
+    /// The user who started this session, via `/analyze_start`
+    pub owner: UserId,
     /// Audio recorder
     pub recorder: Arc<UserRecorder>,
     /// Voice call handle
@@ -33,6 +43,8 @@ pub struct GuildSession {
     pub last_context: RwLock<String>,
     /// Recording task handle
     pub task_handle: Option<JoinHandle<()>>,
+    /// Periodic checkpoint-to-disk task handle
+    pub checkpoint_handle: Option<JoinHandle<()>>,
     /// Whether the session is active
     pub is_active: bool,
 }
@@ -42,23 +54,33 @@ impl GuildSession {
     pub fn new(
         guild_id: GuildId,
         text_channel_id: ChannelId,
+        voice_channel_id: ChannelId,
+        owner: UserId,
         call: Arc<tokio::sync::Mutex<Call>>,
         temp_dir: &PathBuf,
     ) -> Result<Self, crate::audio::recorder::RecorderError> {
-        let recorder = Arc::new(UserRecorder::new(temp_dir)?);
+        let recorder = Arc::new(UserRecorder::new(guild_id.get(), temp_dir)?);
 
         Ok(Self {
             guild_id,
             text_channel_id,
+            voice_channel_id,
+            owner,
             recorder,
             call,
             user_names: DashMap::new(),
             last_context: RwLock::new(String::new()),
             task_handle: None,
+            checkpoint_handle: None,
             is_active: true,
         })
     }
 
+    /// The user who started this session
+    pub fn owner(&self) -> UserId {
+        self.owner
+    }
+
     /// Register a user's display name
     pub fn register_user(&self, user_id: UserId, name: String) {
         self.user_names.insert(user_id, name);
@@ -78,6 +100,7 @@ impl GuildSession {
         http: Arc<Http>,
         analyzer: Arc<Analyzer>,
         db: Arc<Database>,
+        tts: Arc<TtsSynthesizer>,
     ) -> JoinHandle<()> {
         tokio::spawn(async move {
             loop {
@@ -93,7 +116,7 @@ impl GuildSession {
                     break;
                 }
 
-                let settings = db.get_guild_settings(guild_id.get()).unwrap_or_default();
+                let settings = db.get_guild_settings(guild_id.get()).await.unwrap_or_default();
                 let interval_secs = settings.recording_interval;
 
                 // Wait for interval
@@ -113,6 +136,7 @@ impl GuildSession {
                     http.clone(),
                     analyzer.clone(),
                     db.clone(),
+                    tts.clone(),
                     false,
                 ).await {
                     warn!("Periodic analysis failed: {}", e);
@@ -121,12 +145,27 @@ impl GuildSession {
         })
     }
 
+    /// Start the periodic checkpoint-to-disk loop
+    pub fn start_checkpoint_loop(recorder: Arc<UserRecorder>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(CHECKPOINT_INTERVAL_SECS)).await;
+                if let Err(e) = recorder.checkpoint_to_disk() {
+                    warn!("Failed to checkpoint audio to disk: {}", e);
+                }
+            }
+        })
+    }
+
     /// Stop the session
     pub async fn stop(&mut self) {
         self.is_active = false;
         if let Some(handle) = self.task_handle.take() {
             handle.abort();
         }
+        if let Some(handle) = self.checkpoint_handle.take() {
+            handle.abort();
+        }
     }
 }
 
@@ -136,25 +175,31 @@ pub async fn perform_analysis(
     http: Arc<Http>,
     analyzer: Arc<Analyzer>,
     db: Arc<Database>,
+    tts: Arc<TtsSynthesizer>,
     is_final: bool,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let (guild_id, text_channel_id, recorder, user_names);
+    let (guild_id, text_channel_id, recorder, user_names, call);
     let context;
-    
+
     {
         let session = session.read().await;
         guild_id = session.guild_id;
         text_channel_id = session.text_channel_id;
         recorder = session.recorder.clone();
         user_names = session.user_names.clone();
+        call = session.call.clone();
         context = session.last_context.read().await.clone();
     }
 
     info!("[{}] Starting analysis (Final: {})", guild_id, is_final);
 
-    // Flush audio to files
-    let audio_files = match recorder.flush_audio().await {
-        Ok(files) => files,
+    // Get settings (needed before flushing, to know the archival format)
+    let settings = db.get_guild_settings(guild_id.get()).await.unwrap_or_default();
+
+    // Flush audio to files, along with the merged speaking-segment timeline
+    // derived from each user's frame capture times
+    let (audio_files, timeline) = match recorder.flush_audio(settings.recording_format).await {
+        Ok(result) => result,
         Err(e) => {
             if is_final {
                 info!("[{}] No audio to analyze for final report", guild_id);
@@ -179,9 +224,11 @@ pub async fn perform_analysis(
         })
         .collect();
 
-    // Get settings
-    let settings = db.get_guild_settings(guild_id.get()).unwrap_or_default();
     let mode = settings.analysis_mode;
+    let model = match settings.gemini_model.as_str() {
+        "pro" => crate::config::models::GEMINI_PRO,
+        _ => crate::config::models::GEMINI_FLASH,
+    };
 
     // Send "analyzing" message
     let timestamp = chrono::Utc::now().format("%Y-%m-%d %H:%M").to_string();
@@ -208,7 +255,7 @@ pub async fn perform_analysis(
     thread.send_message(&http, analyzing_msg).await?;
 
     // Run analysis
-    let report = match analyzer.analyze_discussion(audio_files.clone(), &context, user_map, mode).await {
+    let report = match analyzer.analyze_discussion(guild_id.get(), audio_files.clone(), &timeline, &context, user_map, mode, model).await {
         Ok(r) => r,
         Err(crate::analyzer::AnalyzerError::RateLimitExceeded) => {
             "⚠️ 分析のリクエスト制限（Quota Limit）に達しました。".to_string()
@@ -219,14 +266,18 @@ pub async fn perform_analysis(
     };
 
     // Update context
+    let new_context = if report.len() > 2000 {
+        report[report.len() - 2000..].to_string()
+    } else {
+        report.clone()
+    };
     {
         let session = session.read().await;
         let mut last_context = session.last_context.write().await;
-        *last_context = if report.len() > 2000 {
-            report[report.len()-2000..].to_string()
-        } else {
-            report.clone()
-        };
+        *last_context = new_context.clone();
+    }
+    if let Err(e) = db.update_session_context(guild_id.get(), &new_context).await {
+        warn!("[{}] Failed to persist session context: {}", guild_id, e);
     }
 
     // Post report
@@ -247,6 +298,13 @@ pub async fn perform_analysis(
         }
     }
 
+    // Speak the report back into the voice channel, if the guild opted in
+    if settings.voice_feedback_enabled {
+        if let Err(e) = tts.speak_report(&report, call).await {
+            warn!("[{}] Failed to speak report via TTS: {}", guild_id, e);
+        }
+    }
+
     // Cleanup audio files
     let files_to_cleanup: Vec<PathBuf> = audio_files.values().cloned().collect();
     AudioProcessor::cleanup_files(&files_to_cleanup);
@@ -260,18 +318,21 @@ pub struct SessionManager {
     config: Arc<Config>,
     db: Arc<Database>,
     analyzer: Arc<Analyzer>,
+    tts: Arc<TtsSynthesizer>,
 }
 
 impl SessionManager {
     /// Create a new session manager
     pub fn new(config: Arc<Config>, db: Arc<Database>) -> Self {
         let analyzer = Arc::new(Analyzer::new(config.gemini_api_key.clone()));
-        
+        let tts = Arc::new(TtsSynthesizer::new(config.tts_endpoint.clone(), Some(config.gemini_api_key.clone())));
+
         Self {
             sessions: DashMap::new(),
             config,
             db,
             analyzer,
+            tts,
         }
     }
 
@@ -280,37 +341,141 @@ impl SessionManager {
         self.sessions.get(&guild_id).map(|r| r.value().clone())
     }
 
-    /// Create a new session
-    pub fn create_session(
+    /// Take an already-built `GuildSession`, attach its voice event handlers,
+    /// start its checkpoint loop and register it in `self.sessions`. Shared by
+    /// `create_session` (a brand-new session) and `resume_sessions` (restoring
+    /// one that survived a restart).
+    async fn register_session(
+        &self,
+        mut session: GuildSession,
+        call: Arc<tokio::sync::Mutex<Call>>,
+    ) -> Result<Arc<RwLock<GuildSession>>, crate::audio::recorder::RecorderError> {
+        // Attach event handlers to the voice call: VoiceTick for audio frames,
+        // SpeakingStateUpdate/ClientDisconnect so UserRecorder can resolve
+        // SSRC -> real UserId for speaker attribution
+        {
+            let mut handler = call.lock().await;
+            for event in [
+                songbird::CoreEvent::VoiceTick,
+                songbird::CoreEvent::SpeakingStateUpdate,
+                songbird::CoreEvent::ClientDisconnect,
+            ] {
+                handler.add_global_event(
+                    event.into(),
+                    crate::bot::VoiceReceiver {
+                        recorder: session.recorder.clone(),
+                    },
+                );
+            }
+        }
+
+        session.checkpoint_handle = Some(GuildSession::start_checkpoint_loop(session.recorder.clone()));
+
+        let guild_id = session.guild_id;
+        let session = Arc::new(RwLock::new(session));
+        self.sessions.insert(guild_id, session.clone());
+
+        Ok(session)
+    }
+
+    /// Create a new session, owned by `owner` for the purposes of
+    /// `force_analysis`/`cleanup_session` access control
+    pub async fn create_session(
         &self,
         guild_id: GuildId,
         text_channel_id: ChannelId,
+        voice_channel_id: ChannelId,
+        owner: UserId,
         call: Arc<tokio::sync::Mutex<Call>>,
     ) -> Result<Arc<RwLock<GuildSession>>, crate::audio::recorder::RecorderError> {
         let session = GuildSession::new(
             guild_id,
             text_channel_id,
+            voice_channel_id,
+            owner,
             call.clone(),
             &self.config.temp_audio_dir,
         )?;
-        
-        // Attach event handler to the voice call
-        {
-            let mut handler = call.lock().await;
-            handler.add_global_event(
-                songbird::CoreEvent::VoiceTick.into(),
-                crate::bot::VoiceReceiver {
-                    recorder: session.recorder.clone(),
-                },
-            );
+        let session_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let session = self.register_session(session, call).await?;
+
+        // Persist the session so it can be resumed if the bot restarts before
+        // `/analyze_stop` is called
+        let persisted = PersistedSession {
+            guild_id: guild_id.get(),
+            text_channel_id: text_channel_id.get(),
+            voice_channel_id: voice_channel_id.get(),
+            owner: owner.get(),
+            session_timestamp,
+            last_context: String::new(),
+        };
+        if let Err(e) = self.db.save_session(&persisted).await {
+            warn!("[{}] Failed to persist session: {}", guild_id, e);
         }
 
-        let session = Arc::new(RwLock::new(session));
-        self.sessions.insert(guild_id, session.clone());
-        
         Ok(session)
     }
 
+    /// Rejoin and restore every session still marked active from before the
+    /// bot last shut down, so a crash or redeploy doesn't orphan an
+    /// in-progress recording. Call once at startup, after songbird is
+    /// registered with the client.
+    pub async fn resume_sessions(
+        &self,
+        serenity_ctx: &serenity::client::Context,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let persisted_sessions = self.db.list_sessions().await?;
+        if persisted_sessions.is_empty() {
+            return Ok(());
+        }
+
+        let manager = songbird::get(serenity_ctx)
+            .await
+            .ok_or("Songbird not registered")?;
+
+        for persisted in persisted_sessions {
+            let guild_id = GuildId::new(persisted.guild_id);
+            let text_channel_id = ChannelId::new(persisted.text_channel_id);
+            let voice_channel_id = ChannelId::new(persisted.voice_channel_id);
+            let owner = UserId::new(persisted.owner);
+
+            info!("[{}] Resuming session in voice channel {}", guild_id, voice_channel_id);
+
+            let call = match manager.join(guild_id, voice_channel_id).await {
+                Ok(call) => call,
+                Err(e) => {
+                    error!("[{}] Failed to rejoin voice channel on resume: {}", guild_id, e);
+                    continue;
+                }
+            };
+
+            let mut session = match GuildSession::new(
+                guild_id,
+                text_channel_id,
+                voice_channel_id,
+                owner,
+                call.clone(),
+                &self.config.temp_audio_dir,
+            ) {
+                Ok(session) => session,
+                Err(e) => {
+                    error!("[{}] Failed to rebuild session on resume: {}", guild_id, e);
+                    continue;
+                }
+            };
+            *session.last_context.get_mut() = persisted.last_context.clone();
+
+            let _session = self.register_session(session, call).await?;
+            self.start_analysis_loop(guild_id, serenity_ctx.http.clone());
+        }
+
+        Ok(())
+    }
+
     /// Start analysis loop for a session
     pub fn start_analysis_loop(&self, guild_id: GuildId, http: Arc<Http>) {
         if let Some(session) = self.get_session(guild_id) {
@@ -319,6 +484,7 @@ impl SessionManager {
                 http,
                 self.analyzer.clone(),
                 self.db.clone(),
+                self.tts.clone(),
             );
             
             // Store handle
@@ -329,26 +495,72 @@ impl SessionManager {
         }
     }
 
-    /// Force analysis for a session
-    pub async fn force_analysis(&self, guild_id: GuildId, http: Arc<Http>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// Check whether `requester` may force-analyze or stop `session`: allowed
+    /// unless the guild has opted into `stop_requires_owner_or_admin` and the
+    /// requester is neither the session owner nor a guild admin.
+    async fn check_authorized(
+        &self,
+        session: &Arc<RwLock<GuildSession>>,
+        guild_id: GuildId,
+        requester: UserId,
+        is_admin: bool,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let settings = self.db.get_guild_settings(guild_id.get()).await.unwrap_or_default();
+        if !settings.stop_requires_owner_or_admin {
+            return Ok(());
+        }
+
+        let owner = session.read().await.owner();
+        if owner == requester || is_admin {
+            Ok(())
+        } else {
+            Err("Only the session owner or a guild admin can do that".into())
+        }
+    }
+
+    /// Force analysis for a session, if `requester` is authorized
+    pub async fn force_analysis(
+        &self,
+        guild_id: GuildId,
+        requester: UserId,
+        is_admin: bool,
+        http: Arc<Http>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         if let Some(session) = self.get_session(guild_id) {
-            perform_analysis(session, http, self.analyzer.clone(), self.db.clone(), false).await
+            self.check_authorized(&session, guild_id, requester, is_admin).await?;
+            perform_analysis(session, http, self.analyzer.clone(), self.db.clone(), self.tts.clone(), false).await
         } else {
             Err("Session not found".into())
         }
     }
 
-    /// Stop and cleanup a session
-    pub async fn cleanup_session(&self, guild_id: GuildId, http: Arc<Http>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        if let Some((_, session)) = self.sessions.remove(&guild_id) {
-            // Run final analysis
-            perform_analysis(session.clone(), http, self.analyzer.clone(), self.db.clone(), true).await?;
-            
-            // Stop session
-            let mut session = session.write().await;
-            session.stop().await;
+    /// Stop and cleanup a session, if `requester` is authorized
+    pub async fn cleanup_session(
+        &self,
+        guild_id: GuildId,
+        requester: UserId,
+        is_admin: bool,
+        http: Arc<Http>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Some(session) = self.get_session(guild_id) else {
+            return Ok(());
+        };
+        self.check_authorized(&session, guild_id, requester, is_admin).await?;
+
+        self.sessions.remove(&guild_id);
+
+        // Run final analysis
+        perform_analysis(session.clone(), http, self.analyzer.clone(), self.db.clone(), self.tts.clone(), true).await?;
+
+        // Stop session
+        let mut session = session.write().await;
+        session.stop().await;
+
+        // Nothing left to resume once stopped cleanly
+        if let Err(e) = self.db.delete_session(guild_id.get()).await {
+            warn!("[{}] Failed to delete persisted session: {}", guild_id, e);
         }
-        
+
         Ok(())
     }
 }
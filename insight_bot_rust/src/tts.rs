@@ -0,0 +1,131 @@
+//! Text-to-speech playback of generated reports
+//!
+//! Synthesizes a report string to speech and enqueues it on the same
+//! songbird `Call` used for voice capture, so participants hear the summary
+//! without having to read the text channel.
+
+use reqwest::Client;
+use songbird::input::Input;
+use songbird::Call;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// A line starting with this full-width bracket begins a report section;
+/// chunks are only ever cut in front of such a line.
+const SECTION_OPEN: char = '【';
+/// Chunk size past which the next section starts a new chunk, compared
+/// against the UTF-8 byte length (`str::len`), not a character count.
+const MAX_CHUNK_CHARS: usize = 500;
+
+#[derive(Error, Debug)]
+pub enum TtsError {
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("TTS endpoint not configured")]
+    NotConfigured,
+    #[error("API error: {0}")]
+    Api(String),
+}
+
+/// Synthesizes report text to speech via a configurable HTTP TTS endpoint.
+pub struct TtsSynthesizer {
+    client: Client,
+    endpoint: Option<String>,
+    api_key: Option<String>,
+    /// Held for the duration of `speak_report`, so a periodic analysis and a
+    /// manual `/analyze_now` landing at the same time queue their chunks back
+    /// to back instead of interleaving two reports' audio.
+    play_lock: Mutex<()>,
+}
+
+impl TtsSynthesizer {
+    /// Create a synthesizer backed by a configurable HTTP TTS endpoint
+    /// (e.g. Gemini TTS). `endpoint` of `None` disables synthesis.
+    pub fn new(endpoint: Option<String>, api_key: Option<String>) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            client,
+            endpoint,
+            api_key,
+            play_lock: Mutex::new(()),
+        }
+    }
+
+    /// Split a report into chunks for separate TTS requests, breaking only
+    /// in front of the `【...】` section headers used by our report format:
+    /// sections are gathered into one chunk until the next one would push it
+    /// past `MAX_CHUNK_CHARS`. A single section longer than that limit is not
+    /// split, so its chunk exceeds `MAX_CHUNK_CHARS`.
+    fn split_into_chunks(report: &str) -> Vec<String> {
+        let mut chunks = Vec::new();
+        let mut current = String::new();
+
+        for line in report.lines() {
+            let starts_new_section = line.trim_start().starts_with(SECTION_OPEN);
+            if starts_new_section && !current.is_empty() && current.len() + line.len() > MAX_CHUNK_CHARS {
+                chunks.push(std::mem::take(&mut current));
+            }
+            current.push_str(line);
+            current.push('\n');
+        }
+
+        if !current.trim().is_empty() {
+            chunks.push(current);
+        }
+
+        chunks
+    }
+
+    /// Request synthesized audio for a single chunk of text.
+    async fn synthesize_chunk(&self, text: &str) -> Result<Vec<u8>, TtsError> {
+        let endpoint = self.endpoint.as_ref().ok_or(TtsError::NotConfigured)?;
+
+        let mut request = self.client.post(endpoint).json(&serde_json::json!({ "text": text }));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(TtsError::Api(format!("TTS request failed: {} - {}", status, text)));
+        }
+
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Synthesize `report` and enqueue each chunk on `call`'s track queue so
+    /// segments play back sequentially.
+    pub async fn speak_report(&self, report: &str, call: Arc<Mutex<Call>>) -> Result<(), TtsError> {
+        if self.endpoint.is_none() {
+            return Ok(());
+        }
+
+        let _play_guard = self.play_lock.lock().await;
+
+        for chunk in Self::split_into_chunks(report) {
+            let audio = match self.synthesize_chunk(&chunk).await {
+                Ok(audio) => audio,
+                Err(e) => {
+                    warn!("Failed to synthesize report chunk: {}", e);
+                    continue;
+                }
+            };
+
+            let input: Input = audio.into();
+            let mut call = call.lock().await;
+            call.enqueue_input(input).await;
+        }
+
+        info!("Queued TTS playback of report");
+        Ok(())
+    }
+}